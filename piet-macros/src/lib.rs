@@ -0,0 +1,83 @@
+//! Compiles piet programs into Rust functions at build time, so that no image is decoded when
+//! they run.
+//!
+//! ```ignore
+//! #[macro_use]
+//! extern crate piet_macros;
+//! extern crate piet;
+//!
+//! mod hello {
+//!     piet_program!("testdata/Piet_hello.png");
+//! }
+//!
+//! fn main() {
+//!     let stdin = std::io::stdin();
+//!     hello::run(&mut stdin.lock(), &mut std::io::stdout()).unwrap();
+//! }
+//! ```
+
+extern crate piet;
+extern crate proc_macro;
+
+use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+
+use proc_macro::{TokenStream, TokenTree};
+
+use piet::emit::{self, Target};
+use piet::program::Program;
+use piet::reader;
+
+/// Expands to a function
+/// `pub fn run(input: &mut impl BufRead, output: &mut impl Write) -> Result<(), PietError>`
+/// that runs the program in the image at the given path, relative to the directory of the
+/// calling crate's `Cargo.toml`. The calling crate must depend on `piet` as well.
+#[proc_macro]
+pub fn piet_program(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(tokens) => tokens,
+        Err(msg) => format!("compile_error!({:?});", msg).parse().unwrap(),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let dir = env::var("CARGO_MANIFEST_DIR").map_err(|_| "CARGO_MANIFEST_DIR is not set")?;
+    let path = PathBuf::from(dir).join(parse_path(input)?);
+    let img = reader::read(&path, &reader::Options::default())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let program = Program::new(img).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let mut out = Vec::new();
+    // Including the image makes cargo rebuild the caller whenever it changes
+    writeln!(out, "const _: &[u8] = include_bytes!({:?});", path.display().to_string())
+        .and_then(|_| emit::write(&program, Target::Rust, &emit::Options::default(), &mut out))
+        .map_err(|e| e.to_string())?;
+    String::from_utf8(out).unwrap().parse().map_err(|e| format!("{:?}", e))
+}
+
+/// Reads the single string literal the macro is given.
+fn parse_path(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let lit = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(lit)), None) => lit.to_string(),
+        _ => return Err("piet_program! expects the path of an image".to_string()),
+    };
+    if lit.len() < 2 || !lit.starts_with('"') || !lit.ends_with('"') {
+        return Err(format!("Expected a string literal, found {}", lit));
+    }
+    let mut path = String::new();
+    let mut chars = lit[1..lit.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(c @ '\\') | Some(c @ '"') | Some(c @ '\'') => path.push(c),
+            Some(c) => return Err(format!("Unsupported escape in path: \\{}", c)),
+            None => unreachable!(),
+        }
+    }
+    Ok(path)
+}
@@ -0,0 +1,48 @@
+#[macro_use]
+extern crate piet_macros;
+extern crate piet;
+
+use piet::Interpreter;
+use piet::reader;
+
+mod hello {
+    piet_program!("../testdata/Piet_hello.png");
+}
+
+mod pi {
+    piet_program!("../testdata/piet_pi.png");
+}
+
+mod bottles {
+    piet_program!("../testdata/99bottles.png");
+}
+
+mod artistic_hello {
+    piet_program!("../testdata/artistic_hello.gif");
+}
+
+fn interpret(name: &str) -> String {
+    let path = format!("{}/../testdata/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let img = reader::read(path, &reader::Options::default()).unwrap();
+    let mut interp = Interpreter::with_io(img, &b""[..], Vec::new()).unwrap();
+    interp.run().unwrap();
+    String::from_utf8(interp.output().clone()).unwrap()
+}
+
+/// The output of a compiled program given no input.
+fn output<F>(run: F) -> String
+    where F: FnOnce(&mut &[u8], &mut Vec<u8>) -> Result<(), piet::PietError>
+{
+    let mut output = Vec::new();
+    run(&mut &b""[..], &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn matches_the_interpreter() {
+    assert_eq!(output(|input, out| hello::run(input, out)), interpret("Piet_hello.png"));
+    assert_eq!(output(|input, out| pi::run(input, out)), interpret("piet_pi.png"));
+    assert_eq!(output(|input, out| bottles::run(input, out)), interpret("99bottles.png"));
+    assert_eq!(output(|input, out| artistic_hello::run(input, out)),
+               interpret("artistic_hello.gif"));
+}
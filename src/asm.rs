@@ -0,0 +1,182 @@
+//! Assembles a linear list of commands into a piet program.
+//!
+//! Source files hold one instruction per line, named as `Command` parses them, with the value
+//! to push following `push`. Arguments of `in` and `out` may be given without parentheses, and
+//! anything after a `#` or `;` is a comment:
+//!
+//! ```text
+//! push 72    # H
+//! out char
+//! push 105   # i
+//! out(char)
+//! ```
+//!
+//! Every command becomes one color change along the top row of the image, read left to right.
+//! Each block is filled column by column, so the block is always left through its top right
+//! codel. `push` sizes the block before it to the value, `nop` becomes a white codel, and the
+//! program ends in a trap that has no way out, shown here with the last block as `A`, white as
+//! `W`, black as `B` and the trap as `T`:
+//!
+//! ```text
+//! .. A W W T
+//! .. B B T T
+//! ```
+//!
+//! `pointer` and `switch` are assembled like any other command, but the program only carries on
+//! along the top row if they leave the DP and CC unchanged.
+
+use std::str::FromStr;
+
+use image::{Rgb, RgbImage};
+
+use color::{Color, PIET_COLORS};
+use command::Command;
+
+/// The largest value pushed by a single block. Larger values are built up from smaller ones,
+/// which keeps the image from growing too tall.
+const MAX_LITERAL: u64 = 64;
+
+/// The color of the block the program starts in
+const START_COLOR: (u8, u8, u8) = PIET_COLORS[0];
+
+const WHITE: (u8, u8, u8) = (255, 255, 255);
+const BLACK: (u8, u8, u8) = (0, 0, 0);
+
+fn rgb((r, g, b): (u8, u8, u8)) -> Rgb<u8> {
+    Rgb([r, g, b])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// Pushes any value, including zero and negative ones
+    Push(i64),
+    /// Any other command. `Command::Push` is not allowed here.
+    Op(Command),
+}
+
+impl FromStr for Instruction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Instruction, String> {
+        let mut words = s.split_whitespace();
+        let name = words.next().ok_or("Missing command")?;
+        let arg = words.next();
+        if let Some(extra) = words.next() {
+            return Err(format!("Unexpected argument: {}", extra));
+        }
+        if name.eq_ignore_ascii_case("push") {
+            let arg = arg.ok_or("push requires a value")?;
+            return arg.parse()
+                .map(Instruction::Push)
+                .map_err(|_| format!("Invalid value: {}", arg));
+        }
+        let cmd = match arg {
+            Some(arg) => format!("{}({})", name, arg).parse()?,
+            None => name.parse()?,
+        };
+        Ok(Instruction::Op(cmd))
+    }
+}
+
+/// Parses an assembly source file. Errors include the line number they occurred on.
+pub fn parse(src: &str) -> Result<Vec<Instruction>, String> {
+    let mut instrs = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line = match line.find(['#', ';']) {
+            Some(end) => &line[..end],
+            None => line,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        instrs.push(line.parse().map_err(|e| format!("line {}: {}", i + 1, e))?);
+    }
+    Ok(instrs)
+}
+
+/// Adds the commands pushing `n` to `out`, each paired with the size of the block it leaves.
+fn lower_push(n: u64, out: &mut Vec<(u64, Command)>) {
+    if n == 0 {
+        out.push((1, Command::Push));
+        out.push((1, Command::Not));
+    } else if n <= MAX_LITERAL {
+        out.push((n, Command::Push));
+    } else {
+        lower_push(n / MAX_LITERAL, out);
+        lower_push(MAX_LITERAL, out);
+        out.push((1, Command::Multiply));
+        if !n.is_multiple_of(MAX_LITERAL) {
+            lower_push(n % MAX_LITERAL, out);
+            out.push((1, Command::Add));
+        }
+    }
+}
+
+/// Turns instructions into the commands executed, each paired with the size of the block it
+/// leaves.
+pub fn lower(instrs: &[Instruction]) -> Vec<(u64, Command)> {
+    let mut out = Vec::new();
+    for instr in instrs {
+        match *instr {
+            Instruction::Push(n) if n < 0 => {
+                lower_push(0, &mut out);
+                lower_push(n.unsigned_abs(), &mut out);
+                out.push((1, Command::Subtract));
+            }
+            Instruction::Push(n) => lower_push(n as u64, &mut out),
+            Instruction::Op(cmd) => out.push((1, cmd)),
+        }
+    }
+    out
+}
+
+/// Draws a block of `size` codels of `color`, filling columns top to bottom from `left`.
+/// Returns the column after the block.
+fn draw_block(img: &mut RgbImage, left: u32, size: u64, color: Rgb<u8>) -> u32 {
+    let height = u64::from(img.height());
+    for i in 0..size {
+        img.put_pixel(left + (i / height) as u32, (i % height) as u32, color);
+    }
+    left + size.div_ceil(height) as u32
+}
+
+/// Assembles `instrs` into an image with one pixel per codel.
+pub fn assemble(instrs: &[Instruction]) -> Result<RgbImage, String> {
+    if instrs.contains(&Instruction::Op(Command::Push)) {
+        return Err("push requires a value".to_string());
+    }
+    let cmds = lower(instrs);
+    let largest = cmds.iter().map(|&(size, _)| size).max().unwrap_or(1);
+    let height = ((largest as f64).sqrt().ceil() as u32).max(2);
+    let width: u64 = cmds.iter()
+        .map(|&(size, cmd)| {
+            let columns = size.div_ceil(u64::from(height));
+            if cmd == Command::Nop { columns + 1 } else { columns }
+        })
+        .sum();
+    // The last block, the white corridor and the trap take up another 4 columns
+    let mut img = RgbImage::from_pixel(width as u32 + 4, height, rgb(BLACK));
+
+    let mut color = Color::from_px(&START_COLOR).unwrap();
+    let mut left = 0;
+    for &(size, cmd) in &cmds {
+        left = draw_block(&mut img, left, size, rgb(color.rgb_color));
+        if cmd == Command::Nop {
+            img.put_pixel(left, 0, rgb(WHITE));
+            left += 1;
+        } else {
+            let (hue_change, lightness_change) = cmd.color_change();
+            color = color.shifted(hue_change, lightness_change).unwrap();
+        }
+    }
+    left = draw_block(&mut img, left, 1, rgb(color.rgb_color));
+
+    // Slides into the top right codel of the trap, whose exits all lead into black or off the
+    // edge of the image
+    img.put_pixel(left, 0, rgb(WHITE));
+    img.put_pixel(left + 1, 0, rgb(WHITE));
+    for &(x, y) in &[(left + 2, 0), (left + 1, 1), (left + 2, 1)] {
+        img.put_pixel(x, y, rgb(color.rgb_color));
+    }
+    Ok(img)
+}
@@ -0,0 +1,154 @@
+//! Builds the control flow graph of a program, whose nodes are the states the interpreter can be
+//! in between steps, and writes it out for Graphviz
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use command::Command;
+use program::{Node, Program};
+use trace::direction_char;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// Graphviz DOT
+    #[default]
+    Dot,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "dot" => Ok(Format::Dot),
+            _ => Err(format!("Unknown graph format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: Node,
+    pub to: Node,
+    pub cmd: Command,
+    /// For `Pointer` and `Switch`, how many times the DP is rotated or the CC toggled to get to
+    /// `to`, i.e. the value on top of the stack modulo 4 or 2. Always 0 for other commands.
+    pub turns: usize,
+    /// Whether the edge slides through white rather than executing a command
+    pub through_white: bool,
+}
+
+#[derive(Debug)]
+pub struct Cfg {
+    start: Option<Node>,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    terminals: HashSet<Node>,
+}
+
+impl Cfg {
+    /// Builds the graph of every node reachable from the start of `program`.
+    pub fn new(program: &Program) -> Cfg {
+        let nodes = program.reachable();
+        let mut edges = Vec::new();
+        let mut terminals = HashSet::new();
+        for &node in &nodes {
+            let (id, dp, cc) = node;
+            let exit = match program.block(id).exit(dp, cc) {
+                Some(exit) => exit,
+                None => {
+                    terminals.insert(node);
+                    continue;
+                }
+            };
+            let fans_out = exit.cmd == Command::Pointer || exit.cmd == Command::Switch;
+            for (turns, to) in program.successors(node).into_iter().enumerate() {
+                edges.push(Edge {
+                    from: node,
+                    to,
+                    cmd: exit.cmd,
+                    turns: if fans_out { turns } else { 0 },
+                    through_white: exit.through_white,
+                });
+            }
+        }
+        Cfg {
+            start: program.start(),
+            nodes,
+            edges,
+            terminals,
+        }
+    }
+
+    pub fn start(&self) -> Option<Node> {
+        self.start
+    }
+
+    /// Every node, in breadth-first order from the start.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Whether the program terminates on reaching `node`, as every exit of its block is
+    /// restricted.
+    pub fn is_terminal(&self, node: Node) -> bool {
+        self.terminals.contains(&node)
+    }
+
+    /// Writes the graph in `format`. Nodes are filled with the color of their block, and
+    /// terminal nodes are drawn with a double outline. Slides through white are dashed.
+    pub fn write<W: Write>(&self,
+                           program: &Program,
+                           format: Format,
+                           out: &mut W)
+                           -> io::Result<()> {
+        match format {
+            Format::Dot => self.write_dot(program, out),
+        }
+    }
+
+    fn write_dot<W: Write>(&self, program: &Program, out: &mut W) -> io::Result<()> {
+        let name = |(id, dp, cc): Node| {
+            format!("b{}_{}{}", id, direction_char(dp), direction_char(cc))
+        };
+        writeln!(out, "digraph piet {{")?;
+        writeln!(out, "    node [shape=box, style=filled];")?;
+        if let Some(start) = self.start {
+            writeln!(out, "    start [shape=point];")?;
+            writeln!(out, "    start -> {};", name(start))?;
+        }
+        for &node in &self.nodes {
+            let (id, dp, cc) = node;
+            let (r, g, b) = program.block(id).color.rgb_color;
+            write!(out,
+                   "    {} [label=\"block {}\\n{},{}\", fillcolor=\"#{:02x}{:02x}{:02x}\"",
+                   name(node),
+                   id,
+                   direction_char(dp),
+                   direction_char(cc),
+                   r,
+                   g,
+                   b)?;
+            if self.is_terminal(node) {
+                write!(out, ", peripheries=2")?;
+            }
+            writeln!(out, "];")?;
+        }
+        for edge in &self.edges {
+            write!(out, "    {} -> {} ", name(edge.from), name(edge.to))?;
+            match edge.cmd {
+                _ if edge.through_white => writeln!(out, "[label=\"white\", style=dashed];")?,
+                Command::Pointer | Command::Switch => {
+                    writeln!(out, "[label=\"{} {}\"];", edge.cmd, edge.turns)?
+                }
+                cmd => writeln!(out, "[label=\"{}\"];", cmd)?,
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
@@ -2,6 +2,8 @@ use std::fmt;
 
 use ansi_term::Colour::RGB;
 
+use error::PietError;
+
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Hue {
     NoHue = 0,
@@ -21,7 +23,69 @@ pub enum Lightness {
     Dark = 3,
 }
 
-#[derive(Debug)]
+/// The RGB values of every color a piet program may contain.
+pub static PIET_COLORS: [(u8, u8, u8); 20] = [(255, 192, 192),
+                                              (255, 0, 0),
+                                              (192, 0, 0),
+                                              (255, 255, 192),
+                                              (255, 255, 0),
+                                              (192, 192, 0),
+                                              (192, 255, 192),
+                                              (0, 255, 0),
+                                              (0, 192, 0),
+                                              (192, 255, 255),
+                                              (0, 255, 255),
+                                              (0, 192, 192),
+                                              (192, 192, 255),
+                                              (0, 0, 255),
+                                              (0, 0, 192),
+                                              (255, 192, 255),
+                                              (255, 0, 255),
+                                              (192, 0, 192),
+                                              (255, 255, 255),
+                                              (0, 0, 0)];
+
+/// Converts an sRGB pixel into the CIE L*a*b* color space, in which euclidean distance
+/// approximates how different two colors look.
+fn to_lab(px: &(u8, u8, u8)) -> (f64, f64, f64) {
+    fn linear(c: u8) -> f64 {
+        let c = f64::from(c) / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }
+    fn f(t: f64) -> f64 {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    }
+
+    let (r, g, b) = (linear(px.0), linear(px.1), linear(px.2));
+    // XYZ, normalized to the D65 white point
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+/// Finds the piet color which looks most like `px`.
+pub fn nearest_piet_color(px: &(u8, u8, u8)) -> (u8, u8, u8) {
+    let (l, a, b) = to_lab(px);
+    let distance = |c: &(u8, u8, u8)| {
+        let (cl, ca, cb) = to_lab(c);
+        (l - cl).powi(2) + (a - ca).powi(2) + (b - cb).powi(2)
+    };
+    *PIET_COLORS.iter()
+        .min_by(|c1, c2| distance(c1).partial_cmp(&distance(c2)).unwrap())
+        .unwrap()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
     pub mnemonic: &'static str,
     pub rgb_color: (u8, u8, u8),
@@ -30,7 +94,48 @@ pub struct Color {
 }
 
 impl Color {
-    pub fn from_px(px: &(u8, u8, u8)) -> Result<Color, String> {
+    pub fn is_white(&self) -> bool {
+        self.rgb_color == (255, 255, 255)
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.rgb_color == (0, 0, 0)
+    }
+
+    /// The two letter name npiet uses for the color, e.g. `nR` for red or `dB` for dark blue.
+    pub fn abbreviation(&self) -> String {
+        let lightness = match self.lightness {
+            Lightness::Light => 'l',
+            Lightness::Normal => 'n',
+            Lightness::Dark => 'd',
+            Lightness::NoLightness if self.is_white() => 'W',
+            Lightness::NoLightness => 'B',
+        };
+        let hue = match self.hue {
+            Hue::Red => 'R',
+            Hue::Yellow => 'Y',
+            Hue::Green => 'G',
+            Hue::Cyan => 'C',
+            Hue::Blue => 'B',
+            Hue::Magenta => 'M',
+            Hue::NoHue => lightness,
+        };
+        format!("{}{}", lightness, hue)
+    }
+
+    /// The color `hue_change` steps along the hue cycle and `lightness_change` steps darker,
+    /// such that moving from this color to it executes the command at that offset in
+    /// `COMMAND_MATRIX`. `None` for black and white.
+    pub fn shifted(&self, hue_change: u8, lightness_change: u8) -> Option<Color> {
+        if self.hue == Hue::NoHue {
+            return None;
+        }
+        let hue = (self.hue as usize - 1 + hue_change as usize) % 6;
+        let lightness = (self.lightness as usize - 1 + lightness_change as usize) % 3;
+        Color::from_px(&PIET_COLORS[hue * 3 + lightness]).ok()
+    }
+
+    pub fn from_px(px: &(u8, u8, u8)) -> Result<Color, PietError> {
         match *px {
             (255, 192, 192) => {
                 Ok(Color {
@@ -176,7 +281,28 @@ impl Color {
                     lightness: Lightness::Dark,
                 })
             }
-            _ => Err(String::from(format!("Invalid color specified: {:?}", px))),
+            (255, 255, 255) => {
+                Ok(Color {
+                    mnemonic: "White",
+                    rgb_color: *px,
+                    hue: Hue::NoHue,
+                    lightness: Lightness::NoLightness,
+                })
+            }
+            (0, 0, 0) => {
+                Ok(Color {
+                    mnemonic: "Black",
+                    rgb_color: *px,
+                    hue: Hue::NoHue,
+                    lightness: Lightness::NoLightness,
+                })
+            }
+            _ => {
+                Err(PietError::InvalidColor {
+                    rgb: *px,
+                    pos: None,
+                })
+            }
         }
     }
 }
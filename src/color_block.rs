@@ -1,106 +1,108 @@
-use std::collections::HashSet;
-use std::cmp::Ordering;
-
 use image::RgbImage;
 
+use color::Color;
+use error::PietError;
 use state::{Position, Direction};
 use util;
 
 // See: https://en.wikipedia.org/wiki/Flood_fill
-// Inspired by how npiet does color block checking
-fn flood_check(img: &RgbImage, x: i32, y: i32, mut blk: &mut ColorBlock) {
-    let out_of_bounds = x < 0 || y < 0 || (x as u32) == img.width() || (y as u32) == img.height();
-    if out_of_bounds {
-        return;
-    }
-    let pos = Position {
-        left: x as u32,
-        top: y as u32,
-    };
-    if blk.codels.contains(&pos) {
-        return;
-    }
-
-    if util::get_px(&img, &pos) != blk.color {
-        return;
-    }
+// Uses an explicit worklist rather than recursion, so that blocks of any size can be filled
+// without overflowing the stack.
+// Codels are marked in `visited`, a row-major bitmap of the whole image, as they are claimed.
+fn flood_fill(img: &RgbImage, start: Position, visited: &mut [bool], blk: &mut ColorBlock) {
+    let (width, height) = img.dimensions();
+    let index = |pos: &Position| pos.top as usize * width as usize + pos.left as usize;
+    let mut worklist = vec![start];
+    visited[index(&start)] = true;
 
-    blk.codels.insert(pos);
+    while let Some(pos) = worklist.pop() {
+        blk.codels.push(pos);
 
-    // TODO(perf): Use vecdeque-based method
-    // South
-    flood_check(&img, x, y + 1, &mut blk);
-    // North
-    flood_check(&img, x, y - 1, &mut blk);
-    // East
-    flood_check(&img, x - 1, y, &mut blk);
-    // West
-    flood_check(&img, x + 1, y, &mut blk);
+        for &(dx, dy) in &[(0, 1), (0, -1), (-1, 0), (1, 0)] {
+            let (left, top) = (i64::from(pos.left) + dx, i64::from(pos.top) + dy);
+            if left < 0 || top < 0 || left >= i64::from(width) || top >= i64::from(height) {
+                continue;
+            }
+            let neighbor = Position {
+                left: left as u32,
+                top: top as u32,
+            };
+            let i = index(&neighbor);
+            if !visited[i] && util::get_px(img, &neighbor) == blk.color {
+                visited[i] = true;
+                worklist.push(neighbor);
+            }
+        }
+    }
 }
 
-fn compare_boundary_positions(p1: &Position,
-                              p2: &Position,
-                              sort_x: bool,
-                              reverse: bool)
-                              -> Ordering {
-    let order = if sort_x {
-        p1.left.cmp(&p2.left)
-    } else {
-        p1.top.cmp(&p2.top)
-    };
-    if reverse { order.reverse() } else { order }
+/// How far `pos` lies in `dir`, such that the codel furthest in that direction has the largest
+/// distance.
+fn distance_in_direction(pos: &Position, dir: Direction) -> i64 {
+    match dir {
+        Direction::Right => i64::from(pos.left),
+        Direction::Down => i64::from(pos.top),
+        Direction::Left => -i64::from(pos.left),
+        Direction::Up => -i64::from(pos.top),
+    }
 }
 
 #[derive(Debug)]
 pub struct ColorBlock {
     pub color: (u8, u8, u8),
-    codels: HashSet<Position>,
+    codels: Vec<Position>,
 }
 
 impl ColorBlock {
-    pub fn from_position_in_img(img: &RgbImage, pos: &Position) -> ColorBlock {
+    /// Finds the block containing the codel at `pos`. Fails if that codel is not one of the
+    /// piet colors.
+    pub fn from_position_in_img(img: &RgbImage, pos: &Position) -> Result<ColorBlock, PietError> {
+        let mut visited = vec![false; img.width() as usize * img.height() as usize];
+        ColorBlock::fill(img, pos, &mut visited)
+    }
+
+    /// Like `from_position_in_img`, but shares `visited`, a row-major bitmap of the image, with
+    /// other fills so that a whole image can be split into blocks in a single pass. Codels of
+    /// the block are marked as they are found; `pos` itself must not have been visited yet.
+    pub fn fill(img: &RgbImage,
+                pos: &Position,
+                visited: &mut [bool])
+                -> Result<ColorBlock, PietError> {
         let mut blk = ColorBlock {
             color: (0, 0, 0),
-            codels: HashSet::new(),
+            codels: Vec::new(),
         };
-        let target_color = util::get_px(&img, &pos);
+        let target_color = util::get_px(img, pos);
+        if Color::from_px(&target_color).is_err() {
+            return Err(PietError::InvalidColor {
+                rgb: target_color,
+                pos: Some(*pos),
+            });
+        }
 
         blk.color = target_color;
-        // Note that initial direction here does not matter
-        flood_check(&img, pos.left as i32, pos.top as i32, &mut blk);
-        blk
+        flood_fill(img, *pos, visited, &mut blk);
+        Ok(blk)
     }
 
-    pub fn value(&self) -> i32 {
-        self.codels.len() as i32
+    /// How many codels the block is made of, the value `Push` pushes when leaving it.
+    pub fn value(&self) -> usize {
+        self.codels.len()
     }
 
-    pub fn boundary_codel_position(&self, dp: &Direction, cc: &Direction) -> Position {
-        let initially_sort_x = match *dp {
-            Direction::Up | Direction::Down => true,
-            _ => false,
-        };
-        let subsequently_sort_x = !initially_sort_x;
-        let reverse_first_sort = match (*dp, *cc) {
-            (Direction::Right, Direction::Right) => true,
-            (Direction::Down, Direction::Left) => true,
-            (Direction::Left, Direction::Left) => true,
-            (Direction::Up, Direction::Right) => true,
-            _ => false,
-        };
-        let reverse_second_sort = match *dp {
-            Direction::Right | Direction::Down => true,
-            _ => false,
-        };
-
-        let mut cvec: Vec<&Position> = self.codels.iter().collect();
-        cvec.sort_by(|p1, p2| {
-            compare_boundary_positions(p1, p2, initially_sort_x, reverse_first_sort)
-        });
-        cvec.sort_by(|p1, p2| {
-            compare_boundary_positions(p1, p2, subsequently_sort_x, reverse_second_sort)
-        });
+    pub fn codels(&self) -> &[Position] {
+        &self.codels
+    }
 
-        *cvec[0]
+    /// The codel through which the block is exited: the codel furthest along the DP, picking
+    /// the one furthest to the CC's side of the DP among those.
+    pub fn boundary_codel_position(&self, dp: &Direction, cc: &Direction) -> Position {
+        let codel_dir = *cc + *dp;
+        *self.codels
+            .iter()
+            .max_by_key(|pos| {
+                (distance_in_direction(pos, *dp), distance_in_direction(pos, codel_dir))
+            })
+            .unwrap()
     }
 }
@@ -0,0 +1,141 @@
+//! The commands a piet program is made of, and how they are decoded from color changes
+
+use std::fmt;
+use std::str::FromStr;
+
+use color::{Color, Hue, Lightness};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataType {
+    Number,
+    Char,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Command {
+    Nop,
+    Push,
+    Pop,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Mod,
+    Not,
+    Greater,
+    Pointer,
+    Switch,
+    Duplicate,
+    Roll,
+    In(DataType),
+    Out(DataType),
+}
+
+/// Commands indexed by hue change, then lightness change.
+pub static COMMAND_MATRIX: [[Command; 3]; 6] =
+    [[Command::Nop, Command::Push, Command::Pop],
+     [Command::Add, Command::Subtract, Command::Multiply],
+     [Command::Divide, Command::Mod, Command::Not],
+     [Command::Greater, Command::Pointer, Command::Switch],
+     [Command::Duplicate, Command::Roll, Command::In(DataType::Number)],
+     [Command::In(DataType::Char),
+      Command::Out(DataType::Number),
+      Command::Out(DataType::Char)]];
+
+impl Command {
+    /// The hue and lightness change that executes the command, i.e. its indices in
+    /// `COMMAND_MATRIX`.
+    pub fn color_change(&self) -> (u8, u8) {
+        for (hue_change, row) in COMMAND_MATRIX.iter().enumerate() {
+            if let Some(lightness_change) = row.iter().position(|cmd| cmd == self) {
+                return (hue_change as u8, lightness_change as u8);
+            }
+        }
+        unreachable!("{:?} is missing from COMMAND_MATRIX", self)
+    }
+
+    /// Decodes the command executed when moving from a block of `from` color into a block of
+    /// `to` color.
+    pub fn from_colors(from: &Color, to: &Color) -> Command {
+        let hue_change = match (from.hue, to.hue) {
+            (Hue::Magenta, Hue::Red) => 1,
+            (from_hue, to_hue) => {
+                let (from_i, to_i) = (from_hue as i8, to_hue as i8);
+                if to_i < from_i {
+                    6 - (from_i - to_i)
+                } else {
+                    to_i - from_i
+                }
+            }
+        };
+        let lightness_change = match (from.lightness, to.lightness) {
+            (Lightness::Dark, Lightness::Light) => 1,
+            (from_l, to_l) => {
+                let (from_i, to_i) = (from_l as i8, to_l as i8);
+                if to_i < from_i {
+                    3 - (from_i - to_i)
+                } else {
+                    to_i - from_i
+                }
+            }
+        };
+        COMMAND_MATRIX[hue_change as usize][lightness_change as usize]
+    }
+}
+
+impl fmt::Display for Command {
+    /// Writes the command by the name npiet uses for it in traces.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Command::Nop => "nop",
+            Command::Push => "push",
+            Command::Pop => "pop",
+            Command::Add => "add",
+            Command::Subtract => "sub",
+            Command::Multiply => "mul",
+            Command::Divide => "div",
+            Command::Mod => "mod",
+            Command::Not => "not",
+            Command::Greater => "greater",
+            Command::Pointer => "pointer",
+            Command::Switch => "switch",
+            Command::Duplicate => "duplicate",
+            Command::Roll => "roll",
+            Command::In(DataType::Number) => "in(number)",
+            Command::In(DataType::Char) => "in(char)",
+            Command::Out(DataType::Number) => "out(number)",
+            Command::Out(DataType::Char) => "out(char)",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    /// Parses a command by its name, ignoring case, e.g. `push` or `out(char)`. Some commands
+    /// may also be abbreviated, as in `sub` or `dup`.
+    fn from_str(s: &str) -> Result<Command, String> {
+        match s.to_lowercase().as_str() {
+            "nop" => Ok(Command::Nop),
+            "push" => Ok(Command::Push),
+            "pop" => Ok(Command::Pop),
+            "add" => Ok(Command::Add),
+            "subtract" | "sub" => Ok(Command::Subtract),
+            "multiply" | "mul" => Ok(Command::Multiply),
+            "divide" | "div" => Ok(Command::Divide),
+            "mod" => Ok(Command::Mod),
+            "not" => Ok(Command::Not),
+            "greater" => Ok(Command::Greater),
+            "pointer" => Ok(Command::Pointer),
+            "switch" => Ok(Command::Switch),
+            "duplicate" | "dup" => Ok(Command::Duplicate),
+            "roll" => Ok(Command::Roll),
+            "in(number)" => Ok(Command::In(DataType::Number)),
+            "in(char)" => Ok(Command::In(DataType::Char)),
+            "out(number)" => Ok(Command::Out(DataType::Number)),
+            "out(char)" => Ok(Command::Out(DataType::Char)),
+            _ => Err(format!("Unknown command: {}", s)),
+        }
+    }
+}
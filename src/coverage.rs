@@ -0,0 +1,131 @@
+//! Records which blocks and block exits a run exercised, compared to those the program could
+//! reach at all
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use image::{Rgb, RgbImage};
+
+use interpreter::Step;
+use program::{BlockId, Program};
+use state::Direction;
+use util;
+
+/// Identifies one of the up to 8 ways out of a block, by the DP and CC it is left with.
+pub type Edge = (BlockId, Direction, Direction);
+
+#[derive(Debug)]
+pub struct Coverage {
+    /// Whether each block was entered, indexed by `BlockId`
+    covered_blocks: Vec<bool>,
+    covered_edges: HashSet<Edge>,
+    reachable_blocks: Vec<bool>,
+    reachable_edges: HashSet<Edge>,
+}
+
+impl Coverage {
+    /// Creates an empty coverage record for `program`, working out which blocks and edges are
+    /// reachable from its start.
+    pub fn new(program: &Program) -> Coverage {
+        let mut reachable_blocks = vec![false; program.blocks().len()];
+        let mut reachable_edges = HashSet::new();
+        for (id, dp, cc) in program.reachable() {
+            reachable_blocks[id] = true;
+            if let Some(exit) = program.block(id).exit(dp, cc) {
+                reachable_edges.insert((id, exit.dp, exit.cc));
+            }
+        }
+        let mut covered_blocks = vec![false; program.blocks().len()];
+        if let Some((id, _, _)) = program.start() {
+            covered_blocks[id] = true;
+        }
+        Coverage {
+            covered_blocks,
+            covered_edges: HashSet::new(),
+            reachable_blocks,
+            reachable_edges,
+        }
+    }
+
+    pub fn record(&mut self, program: &Program, step: &Step) {
+        let from = program.block_id(step.from_pos);
+        if let Some(exit) = program.block(from).exit(step.dp_before, step.cc_before) {
+            self.covered_edges.insert((from, exit.dp, exit.cc));
+        }
+        self.covered_blocks[program.block_id(step.to_pos)] = true;
+    }
+
+    pub fn is_covered(&self, id: BlockId) -> bool {
+        self.covered_blocks[id]
+    }
+
+    pub fn is_edge_covered(&self, edge: Edge) -> bool {
+        self.covered_edges.contains(&edge)
+    }
+
+    /// The number of covered blocks and the number of reachable blocks.
+    pub fn blocks(&self) -> (usize, usize) {
+        (self.covered_blocks.iter().filter(|&&covered| covered).count(),
+         self.reachable_blocks.iter().filter(|&&reachable| reachable).count())
+    }
+
+    /// The number of covered edges and the number of reachable edges.
+    pub fn edges(&self) -> (usize, usize) {
+        (self.covered_edges.len(), self.reachable_edges.len())
+    }
+
+    /// Draws the program with every codel scaled up to `scale`x`scale` pixels, dimming the
+    /// colored blocks that were never entered.
+    pub fn annotate(&self, program: &Program, scale: u32) -> RgbImage {
+        program.render(scale, |id, blk| {
+            let (r, g, b) = blk.color.rgb_color;
+            if self.covered_blocks[id] || blk.color.is_white() || blk.color.is_black() {
+                Rgb([r, g, b])
+            } else {
+                util::mix((r, g, b), (128, 128, 128), 0.25)
+            }
+        })
+    }
+
+    /// Writes the percentage of reachable blocks and edges covered, followed by those that were
+    /// not.
+    pub fn write_summary<W: Write>(&self, program: &Program, out: &mut W) -> io::Result<()> {
+        let percent = |(covered, total): (usize, usize)| {
+            if total == 0 {
+                100.0
+            } else {
+                100.0 * covered as f64 / total as f64
+            }
+        };
+        let (blocks, edges) = (self.blocks(), self.edges());
+        writeln!(out,
+                 "Blocks covered: {} of {} reachable ({:.1}%)",
+                 blocks.0,
+                 blocks.1,
+                 percent(blocks))?;
+        writeln!(out,
+                 "Exits covered: {} of {} reachable ({:.1}%)",
+                 edges.0,
+                 edges.1,
+                 percent(edges))?;
+
+        for (id, blk) in program.blocks().iter().enumerate() {
+            if self.reachable_blocks[id] && !self.covered_blocks[id] {
+                writeln!(out, "Never entered: {} block at {}", blk.color.mnemonic, blk.origin)?;
+            }
+        }
+        let mut missed: Vec<_> = self.reachable_edges.difference(&self.covered_edges).collect();
+        missed.sort_by_key(|&&(id, dp, cc)| (id, dp as u8, cc as u8));
+        for &(id, dp, cc) in missed {
+            let blk = program.block(id);
+            writeln!(out,
+                     "Never taken: {} block at {} leaving through {} with DP {:?}, CC {:?}",
+                     blk.color.mnemonic,
+                     blk.origin,
+                     blk.boundary_codel(dp, cc),
+                     dp,
+                     cc)?;
+        }
+        Ok(())
+    }
+}
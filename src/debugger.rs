@@ -0,0 +1,232 @@
+//! An interactive debugger, which steps through a program and stops at breakpoints
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use command::Command;
+use interpreter::{Interpreter, Step};
+use state::Position;
+use value::Value;
+
+const HELP: &str = "\
+step [n]            Execute the next n steps (default 1)
+continue            Run until a breakpoint is hit or the program terminates
+break <x,y>         Stop when entering the block containing codel (x, y)
+break <command>     Stop before executing a command, e.g. `break out(char)`
+print stack         Show the stack, bottom first
+print dp | cc       Show the direction pointer or codel chooser, or both with `print dp/cc`
+where               Show the current block and the next command
+help                Show this message
+quit                Exit the debugger";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops when the block containing this codel is entered
+    Position(Position),
+    /// Stops before this command is executed
+    Command(Command),
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    /// Parses either a codel position such as `3,4` or a command name such as `push`.
+    fn from_str(s: &str) -> Result<Breakpoint, String> {
+        let s = s.trim();
+        match s.find(',') {
+            Some(i) => {
+                let coord = |c: &str| {
+                    c.trim().parse().map_err(|_| format!("Invalid position: {}", s))
+                };
+                Ok(Breakpoint::Position(Position {
+                    left: coord(&s[..i])?,
+                    top: coord(&s[i + 1..])?,
+                }))
+            }
+            None => s.parse().map(Breakpoint::Command),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Breakpoint::Position(pos) => write!(f, "{}", pos),
+            Breakpoint::Command(cmd) => write!(f, "{}", cmd),
+        }
+    }
+}
+
+fn format_values(values: &[Value]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", values.join(", "))
+}
+
+pub struct Debugger<R, W> {
+    interp: Interpreter<R, W>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    pub fn new(interp: Interpreter<R, W>) -> Debugger<R, W> {
+        Debugger {
+            interp,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    pub fn interpreter(&self) -> &Interpreter<R, W> {
+        &self.interp
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Adds a breakpoint. Fails if it is a position outside of the program.
+    pub fn add_breakpoint(&mut self, bp: Breakpoint) -> Result<(), String> {
+        if let Breakpoint::Position(pos) = bp {
+            let img = self.interp.image();
+            if pos.left >= img.width() || pos.top >= img.height() {
+                return Err(format!("{} is outside of the {}x{} program",
+                                   pos,
+                                   img.width(),
+                                   img.height()));
+            }
+        }
+        self.breakpoints.push(bp);
+        Ok(())
+    }
+
+    /// The breakpoint the program is currently stopped at, if any.
+    fn hit_breakpoint(&self) -> Option<Breakpoint> {
+        let program = self.interp.program();
+        let block = program.block_id(self.interp.state().pos);
+        let next_cmd = self.interp.next_command();
+        self.breakpoints
+            .iter()
+            .find(|bp| match **bp {
+                Breakpoint::Position(pos) => program.block_id(pos) == block,
+                Breakpoint::Command(cmd) => next_cmd == Some(cmd),
+            })
+            .cloned()
+    }
+
+    /// Executes up to `limit` steps, or until a breakpoint is hit or the program terminates.
+    /// Every step is written to `out` when `verbose` is set.
+    fn run<O: Write>(&mut self,
+                     limit: Option<u64>,
+                     verbose: bool,
+                     out: &mut O)
+                     -> io::Result<()> {
+        if self.interp.is_finished() {
+            return writeln!(out, "The program is not running");
+        }
+        let mut taken = 0;
+        while limit.is_none_or(|limit| taken < limit) {
+            if taken > 0 {
+                if let Some(bp) = self.hit_breakpoint() {
+                    writeln!(out, "Breakpoint at {}", bp)?;
+                    break;
+                }
+            }
+            match self.interp.next() {
+                Some(ref step) if verbose => self.write_step(step, out)?,
+                Some(_) => (),
+                None => break,
+            }
+            taken += 1;
+        }
+        self.interp.output_mut().flush()?;
+
+        if self.interp.is_finished() {
+            match self.interp.error() {
+                Some(err) => writeln!(out, "The program stopped with an error: {}", err)?,
+                None => {
+                    writeln!(out,
+                             "The program terminated after {} steps",
+                             self.interp.steps())?
+                }
+            }
+        } else if !verbose {
+            self.write_location(out)?;
+        }
+        Ok(())
+    }
+
+    fn write_step<O: Write>(&self, step: &Step, out: &mut O) -> io::Result<()> {
+        writeln!(out,
+                 "{}: {} from {} {} to {} {}, popped {}, pushed {}",
+                 self.interp.steps(),
+                 step.cmd,
+                 step.from_color.mnemonic,
+                 step.from_pos,
+                 step.to_color.mnemonic,
+                 step.to_pos,
+                 format_values(&step.popped),
+                 format_values(&step.pushed))
+    }
+
+    fn write_location<O: Write>(&self, out: &mut O) -> io::Result<()> {
+        let state = self.interp.state();
+        let blk = self.interp.program().block_at(state.pos);
+        writeln!(out,
+                 "At {} in a {} block of {} codels, DP {:?}, CC {:?}, after {} steps",
+                 state.pos,
+                 blk.color.mnemonic,
+                 blk.size,
+                 state.dp(),
+                 state.cc(),
+                 self.interp.steps())?;
+        match self.interp.next_command() {
+            Some(cmd) => writeln!(out, "Next command: {}", cmd),
+            None => writeln!(out, "The program will terminate"),
+        }
+    }
+
+    /// Executes a single line of debugger input, writing any response to `out`. Returns false
+    /// once the debugger should exit.
+    pub fn execute<O: Write>(&mut self, line: &str, out: &mut O) -> io::Result<bool> {
+        let line = line.trim();
+        let (cmd, arg) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        match cmd {
+            "" => (),
+            "step" | "s" => {
+                match if arg.is_empty() { Ok(1) } else { arg.parse() } {
+                    Ok(n) => self.run(Some(n), true, out)?,
+                    Err(_) => writeln!(out, "Invalid step count: {}", arg)?,
+                }
+            }
+            "continue" | "c" => self.run(None, false, out)?,
+            "break" | "b" => {
+                let res = arg.parse().and_then(|bp| {
+                    self.add_breakpoint(bp)?;
+                    Ok(bp)
+                });
+                match res {
+                    Ok(bp) => writeln!(out, "Breakpoint {} at {}", self.breakpoints.len(), bp)?,
+                    Err(e) => writeln!(out, "{}", e)?,
+                }
+            }
+            "print" | "p" => {
+                let state = self.interp.state();
+                match arg {
+                    "stack" => writeln!(out, "{}", format_values(&state.stack))?,
+                    "dp" => writeln!(out, "{:?}", state.dp())?,
+                    "cc" => writeln!(out, "{:?}", state.cc())?,
+                    "dp/cc" => writeln!(out, "DP {:?}, CC {:?}", state.dp(), state.cc())?,
+                    _ => writeln!(out, "Can only print stack, dp or cc")?,
+                }
+            }
+            "where" | "w" => self.write_location(out)?,
+            "help" | "h" => writeln!(out, "{}", HELP)?,
+            "quit" | "q" => return Ok(false),
+            _ => writeln!(out, "Unknown command: {}. Try `help`.", cmd)?,
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,71 @@
+//! Lists the blocks of a program along with where each of their exits leads, giving a textual
+//! view of a program without running it.
+//!
+//! Every colored block is followed by one line per DP/CC combination, written as in npiet's
+//! traces, e.g. `r,l` for DP right and CC left:
+//!
+//! ```text
+//! block 1: DarkRed, 1 codel at (1, 0)
+//!   r,l  exit (1, 0) -> white -> block 3 at (3, 0): nop
+//!   r,r  exit (1, 0) -> white -> block 3 at (3, 0): nop
+//!   d,l  exit (1, 0) as l,r -> block 0 at (0, 0): pop
+//! ```
+//!
+//! `as` gives the DP and CC a block is actually left with after restricted exits were tried,
+//! or that a slide through white ends with.
+
+use std::io::{self, Write};
+
+use program::{BlockId, DIRECTIONS, Program};
+use state::Direction;
+use trace::direction_char;
+
+fn directions(dp: Direction, cc: Direction) -> String {
+    format!("{},{}", direction_char(dp), direction_char(cc))
+}
+
+/// Writes the listing of a single block.
+pub fn write_block<W: Write>(out: &mut W, program: &Program, id: BlockId) -> io::Result<()> {
+    let blk = program.block(id);
+    writeln!(out,
+             "block {}: {}, {} codel{} at {}",
+             id,
+             blk.color.mnemonic,
+             blk.size,
+             if blk.size == 1 { "" } else { "s" },
+             blk.origin)?;
+    if blk.color.is_white() || blk.color.is_black() {
+        return Ok(());
+    }
+    for &(dp, cc) in DIRECTIONS.iter() {
+        write!(out, "  {}  ", directions(dp, cc))?;
+        let exit = match blk.exit(dp, cc) {
+            Some(exit) => exit,
+            None => {
+                writeln!(out, "no exit, the program terminates")?;
+                continue;
+            }
+        };
+        write!(out, "exit {}", exit.from)?;
+        if (exit.dp, exit.cc) != (dp, cc) {
+            write!(out, " as {}", directions(exit.dp, exit.cc))?;
+        }
+        if exit.through_white {
+            write!(out, " -> white")?;
+        }
+        write!(out, " -> block {} at {}", program.block_id(exit.to), exit.to)?;
+        if (exit.dp_after, exit.cc_after) != (exit.dp, exit.cc) {
+            write!(out, " as {}", directions(exit.dp_after, exit.cc_after))?;
+        }
+        writeln!(out, ": {}", exit.cmd)?;
+    }
+    Ok(())
+}
+
+/// Writes the listing of every block of `program`, in order.
+pub fn write_listing<W: Write>(out: &mut W, program: &Program) -> io::Result<()> {
+    for id in 0..program.blocks().len() {
+        write_block(out, program, id)?;
+    }
+    Ok(())
+}
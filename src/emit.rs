@@ -0,0 +1,547 @@
+//! Compiles a program ahead of time into source code for another language.
+//!
+//! Every node of the control flow graph reachable from the start becomes a label that executes
+//! the command leaving it and jumps to the next node. `Pointer` and `Switch` dispatch on the
+//! value they pop to one of their successors. In C, values are 64 bit integers, so programs
+//! that rely on the `bigint` feature behave as they would without it. Rust code executes
+//! commands through `interpreter::execute`, and so behaves exactly like the interpreter.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use command::{Command, DataType};
+use program::{Node, Program};
+use trace::direction_char;
+use value::OverflowPolicy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Target {
+    /// A standalone C99 program, for compilers with GCC's overflow builtins
+    #[default]
+    C,
+    /// A Rust function `run(input, output)`, which calls into this crate as `::piet`
+    Rust,
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Target, String> {
+        match s {
+            "c" => Ok(Target::C),
+            "rust" => Ok(Target::Rust),
+            _ => Err(format!("Unknown target: {}", s)),
+        }
+    }
+}
+
+/// How the compiled program handles overflow, as the interpreter's setting of the same name
+/// does
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub overflow: OverflowPolicy,
+}
+
+/// The stack machine every compiled C program starts with. Each command leaves the stack
+/// untouched in the same cases the interpreter does.
+const C_RUNTIME: &str = r#"#include <inttypes.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+/* Not every program uses every command */
+#define RUNTIME static __attribute__((unused))
+
+static int64_t *stack;
+static size_t len, cap;
+
+static int on_overflow(int64_t *res, int64_t saturated, const char *cmd, const char *pos);
+
+RUNTIME void fail(const char *msg, const char *cmd, const char *pos)
+{
+    fflush(stdout);
+    fprintf(stderr, "error: %s in %s at %s\n", msg, cmd, pos);
+    exit(1);
+}
+
+RUNTIME void push(int64_t v)
+{
+    if (len == cap) {
+        cap = cap ? cap * 2 : 64;
+        stack = realloc(stack, cap * sizeof *stack);
+        if (!stack) {
+            fputs("error: out of memory\n", stderr);
+            exit(1);
+        }
+    }
+    stack[len++] = v;
+}
+
+/* The remainder taking the sign of the divisor, which must not be 0 */
+RUNTIME int64_t floor_mod(int64_t a, int64_t b)
+{
+    int64_t rem = b == -1 ? 0 : a % b;
+    return rem != 0 && (rem < 0) != (b < 0) ? rem + b : rem;
+}
+
+/* Replaces the top two values with v */
+RUNTIME void replace2(int64_t v)
+{
+    len--;
+    stack[len - 1] = v;
+}
+
+RUNTIME void pop(void)
+{
+    if (len)
+        len--;
+}
+
+RUNTIME void add(const char *pos)
+{
+    int64_t a, b, res;
+    if (len < 2)
+        return;
+    a = stack[len - 2];
+    b = stack[len - 1];
+    if (__builtin_add_overflow(a, b, &res) &&
+        !on_overflow(&res, b > 0 ? INT64_MAX : INT64_MIN, "Add", pos))
+        return;
+    replace2(res);
+}
+
+RUNTIME void sub(const char *pos)
+{
+    int64_t a, b, res;
+    if (len < 2)
+        return;
+    a = stack[len - 2];
+    b = stack[len - 1];
+    if (__builtin_sub_overflow(a, b, &res) &&
+        !on_overflow(&res, b < 0 ? INT64_MAX : INT64_MIN, "Subtract", pos))
+        return;
+    replace2(res);
+}
+
+RUNTIME void mul(const char *pos)
+{
+    int64_t a, b, res;
+    if (len < 2)
+        return;
+    a = stack[len - 2];
+    b = stack[len - 1];
+    if (__builtin_mul_overflow(a, b, &res) &&
+        !on_overflow(&res, (a < 0) != (b < 0) ? INT64_MIN : INT64_MAX, "Multiply", pos))
+        return;
+    replace2(res);
+}
+
+RUNTIME void divide(void)
+{
+    int64_t a, b;
+    if (len < 2)
+        return;
+    a = stack[len - 2];
+    b = stack[len - 1];
+    if (b == 0 || (a == INT64_MIN && b == -1))
+        return;
+    replace2(a / b);
+}
+
+RUNTIME void mod(void)
+{
+    if (len < 2 || stack[len - 1] == 0)
+        return;
+    replace2(floor_mod(stack[len - 2], stack[len - 1]));
+}
+
+RUNTIME void not(void)
+{
+    if (len)
+        stack[len - 1] = !stack[len - 1];
+}
+
+RUNTIME void greater(void)
+{
+    if (len >= 2)
+        replace2(stack[len - 2] > stack[len - 1]);
+}
+
+/* How many times to rotate the DP clockwise */
+RUNTIME int pointer(void)
+{
+    return len ? (int)floor_mod(stack[--len], 4) : 0;
+}
+
+/* Whether to toggle the CC */
+RUNTIME int switch_cc(void)
+{
+    return len ? (int)floor_mod(stack[--len], 2) : 0;
+}
+
+RUNTIME void duplicate(void)
+{
+    if (len)
+        push(stack[len - 1]);
+}
+
+RUNTIME void reverse(int64_t *from, int64_t *to)
+{
+    while (from < --to) {
+        int64_t tmp = *from;
+        *from++ = *to;
+        *to = tmp;
+    }
+}
+
+RUNTIME void roll(void)
+{
+    int64_t depth, rolls, *start;
+    if (len < 2)
+        return;
+    depth = stack[len - 2];
+    if (depth <= 0 || depth > (int64_t)(len - 2))
+        return;
+    rolls = floor_mod(stack[len - 1], depth);
+    len -= 2;
+    start = stack + len - depth;
+    reverse(start, stack + len);
+    reverse(start, start + rolls);
+    reverse(start + rolls, stack + len);
+}
+
+/* Bytes of input read ahead and put back, the next one last */
+static int *unread;
+static size_t unread_len, unread_cap;
+
+RUNTIME int next_byte(void)
+{
+    return unread_len ? unread[--unread_len] : getchar();
+}
+
+RUNTIME void unread_byte(int c)
+{
+    if (c == EOF)
+        return;
+    if (unread_len == unread_cap) {
+        unread_cap = unread_cap ? unread_cap * 2 : 16;
+        unread = realloc(unread, unread_cap * sizeof *unread);
+    }
+    unread[unread_len++] = c;
+}
+
+/* The bytes u8::is_ascii_whitespace accepts */
+RUNTIME int is_space(int c)
+{
+    return c == ' ' || c == '\t' || c == '\n' || c == '\f' || c == '\r';
+}
+
+RUNTIME int is_digit(int c)
+{
+    return c >= '0' && c <= '9';
+}
+
+/* Reads a signed decimal integer. Unless one that fits is read, everything after the
+ * whitespace is put back. */
+RUNTIME void in_number(void)
+{
+    static char *text;
+    static size_t cap;
+    size_t len_text = 0, start, i;
+    int c, neg, overflowed = 0;
+    int64_t n = 0;
+    fflush(stdout);
+    while ((c = next_byte()) != EOF && is_space(c))
+        ;
+    for (; is_digit(c) || (!len_text && (c == '-' || c == '+')); c = next_byte()) {
+        if (len_text == cap) {
+            cap = cap ? cap * 2 : 32;
+            text = realloc(text, cap);
+        }
+        text[len_text++] = (char)c;
+    }
+    unread_byte(c);
+    start = len_text && !is_digit(text[0]);
+    neg = start && text[0] == '-';
+    /* Accumulate negatively, so that INT64_MIN can be read */
+    for (i = start; i < len_text; i++) {
+        if (n < (INT64_MIN + (text[i] - '0')) / 10)
+            overflowed = 1;
+        else
+            n = n * 10 - (text[i] - '0');
+    }
+    if (len_text == start || overflowed || (!neg && n == INT64_MIN)) {
+        while (len_text)
+            unread_byte(text[--len_text]);
+        return;
+    }
+    push(neg ? n : -n);
+}
+
+/* Reads a UTF-8 encoded character, decoding invalid sequences as U+FFFD */
+RUNTIME void in_char(void)
+{
+    int c, n, i;
+    int64_t cp;
+    fflush(stdout);
+    if ((c = next_byte()) == EOF)
+        return;
+    if (c < 0x80) {
+        push(c);
+        return;
+    } else if (c >= 0xc0 && c <= 0xdf) {
+        n = 2;
+        cp = c & 0x1f;
+    } else if (c >= 0xe0 && c <= 0xef) {
+        n = 3;
+        cp = c & 0x0f;
+    } else if (c >= 0xf0 && c <= 0xf7) {
+        n = 4;
+        cp = c & 0x07;
+    } else {
+        push(0xfffd);
+        return;
+    }
+    for (i = 1; i < n; i++) {
+        if ((c = next_byte()) == EOF)
+            break;
+        if ((c & 0xc0) != 0x80) {
+            unread_byte(c);
+            break;
+        }
+        cp = cp << 6 | (c & 0x3f);
+    }
+    if (i < n || cp < (n == 2 ? 0x80 : n == 3 ? 0x800 : 0x10000) || cp > 0x10ffff ||
+        (cp >= 0xd800 && cp <= 0xdfff))
+        cp = 0xfffd;
+    push(cp);
+}
+
+RUNTIME void out_number(void)
+{
+    if (len)
+        printf("%" PRId64, stack[--len]);
+}
+
+RUNTIME void out_char(void)
+{
+    int64_t c;
+    if (!len)
+        return;
+    c = stack[--len];
+    if (c < 0 || c > 0x10ffff || (c >= 0xd800 && c <= 0xdfff))
+        return;
+    if (c < 0x80) {
+        putchar((int)c);
+    } else if (c < 0x800) {
+        putchar((int)(0xc0 | c >> 6));
+        putchar((int)(0x80 | (c & 0x3f)));
+    } else if (c < 0x10000) {
+        putchar((int)(0xe0 | c >> 12));
+        putchar((int)(0x80 | (c >> 6 & 0x3f)));
+        putchar((int)(0x80 | (c & 0x3f)));
+    } else {
+        putchar((int)(0xf0 | c >> 18));
+        putchar((int)(0x80 | (c >> 12 & 0x3f)));
+        putchar((int)(0x80 | (c >> 6 & 0x3f)));
+        putchar((int)(0x80 | (c & 0x3f)));
+    }
+}
+"#;
+
+/// Writes `program` as source code for `target`.
+pub fn write<W: Write>(program: &Program,
+                       target: Target,
+                       opts: &Options,
+                       out: &mut W)
+                       -> io::Result<()> {
+    match target {
+        Target::C => write_c(program, opts, out),
+        Target::Rust => write_rust(program, opts, out),
+    }
+}
+
+/// The body of the function deciding the result of an overflowing `Add`, `Subtract` or
+/// `Multiply`, which returns 0 if the stack should be left untouched. `res` already holds the
+/// wrapped result.
+fn c_overflow_body(policy: OverflowPolicy) -> &'static str {
+    match policy {
+        OverflowPolicy::Wrap => "    return 1;",
+        OverflowPolicy::Saturate => "    *res = saturated;\n    return 1;",
+        OverflowPolicy::Ignore => "    return 0;",
+        OverflowPolicy::Abort => "    fail(\"Arithmetic overflow\", cmd, pos);\n    return 0;",
+    }
+}
+
+/// Numbers nodes in the order they are written, starting with the node the program starts in.
+fn labels(nodes: &[Node]) -> HashMap<Node, usize> {
+    nodes.iter().enumerate().map(|(i, &node)| (node, i)).collect()
+}
+
+fn write_c<W: Write>(program: &Program, opts: &Options, out: &mut W) -> io::Result<()> {
+    let nodes = program.reachable();
+    let labels = labels(&nodes);
+
+    writeln!(out, "/* Compiled from a piet program */")?;
+    write!(out, "{}", C_RUNTIME)?;
+    writeln!(out,
+             "\nstatic int on_overflow(int64_t *res, int64_t saturated, const char *cmd, \
+              const char *pos)")?;
+    writeln!(out, "{{")?;
+    writeln!(out, "    (void)res, (void)saturated, (void)cmd, (void)pos;")?;
+    writeln!(out, "{}", c_overflow_body(opts.overflow))?;
+    writeln!(out, "}}")?;
+
+    writeln!(out, "\nint main(void)\n{{")?;
+    let mut terminates = false;
+    if !nodes.is_empty() {
+        writeln!(out, "    goto s0;")?;
+    }
+    for &node in &nodes {
+        let (id, dp, cc) = node;
+        writeln!(out,
+                 "s{}: /* block {}, {},{} */",
+                 labels[&node],
+                 id,
+                 direction_char(dp),
+                 direction_char(cc))?;
+        let exit = match program.block(id).exit(dp, cc) {
+            Some(exit) => exit,
+            None => {
+                terminates = true;
+                writeln!(out, "    goto end;")?;
+                continue;
+            }
+        };
+        let cmd = exit.cmd;
+        let pos = format!("\"{}\"", exit.from);
+        let successors = program.successors(node);
+        let call = match cmd {
+            _ if exit.through_white => None,
+            Command::Pointer | Command::Switch => {
+                let dispatch = if cmd == Command::Pointer { "pointer" } else { "switch_cc" };
+                writeln!(out, "    switch ({}()) {{", dispatch)?;
+                for (turns, to) in successors.iter().enumerate() {
+                    if turns + 1 == successors.len() {
+                        writeln!(out, "    default: goto s{};", labels[to])?;
+                    } else {
+                        writeln!(out, "    case {}: goto s{};", turns, labels[to])?;
+                    }
+                }
+                writeln!(out, "    }}")?;
+                continue;
+            }
+            Command::Nop => None,
+            Command::Push => Some(format!("push({})", program.block(id).size)),
+            Command::Pop => Some("pop()".to_string()),
+            Command::Add => Some(format!("add({})", pos)),
+            Command::Subtract => Some(format!("sub({})", pos)),
+            Command::Multiply => Some(format!("mul({})", pos)),
+            Command::Divide => Some("divide()".to_string()),
+            Command::Mod => Some("mod()".to_string()),
+            Command::Not => Some("not()".to_string()),
+            Command::Greater => Some("greater()".to_string()),
+            Command::Duplicate => Some("duplicate()".to_string()),
+            Command::Roll => Some("roll()".to_string()),
+            Command::In(DataType::Number) => Some("in_number()".to_string()),
+            Command::In(DataType::Char) => Some("in_char()".to_string()),
+            Command::Out(DataType::Number) => Some("out_number()".to_string()),
+            Command::Out(DataType::Char) => Some("out_char()".to_string()),
+        };
+        if let Some(call) = call {
+            writeln!(out, "    {};", call)?;
+        }
+        writeln!(out, "    goto s{};", labels[&successors[0]])?;
+    }
+    if terminates {
+        writeln!(out, "end:")?;
+    }
+    writeln!(out, "    fflush(stdout);")?;
+    writeln!(out, "    return 0;")?;
+    writeln!(out, "}}")
+}
+
+/// The path of `cmd` with `Command` and `DataType` in scope.
+fn rust_command(cmd: Command) -> String {
+    match cmd {
+        Command::In(dtype) => format!("Command::In(DataType::{:?})", dtype),
+        Command::Out(dtype) => format!("Command::Out(DataType::{:?})", dtype),
+        _ => format!("Command::{:?}", cmd),
+    }
+}
+
+fn write_rust<W: Write>(program: &Program, opts: &Options, out: &mut W) -> io::Result<()> {
+    let nodes = program.reachable();
+    let labels = labels(&nodes);
+
+    writeln!(out,
+             "/// Runs a compiled piet program, reading from `input` and writing to `output`.")?;
+    writeln!(out, "#[allow(unreachable_code)]")?;
+    writeln!(out, "pub fn run(input: &mut impl ::std::io::BufRead,")?;
+    writeln!(out, "           output: &mut impl ::std::io::Write)")?;
+    writeln!(out, "           -> ::std::result::Result<(), ::piet::PietError> {{")?;
+    if nodes.is_empty() {
+        writeln!(out, "    let _ = input;")?;
+        writeln!(out, "    output.flush()?;")?;
+        return writeln!(out, "    Ok(())\n}}");
+    }
+    writeln!(out, "    use ::piet::{{Command, DataType, OverflowPolicy}};")?;
+    writeln!(out, "    use ::piet::interpreter::execute;")?;
+    writeln!(out, "    use ::piet::state::Position;")?;
+    writeln!(out, "    let mut input = ::piet::input::Pushback::new(input);")?;
+    writeln!(out, "    let mut stack = ::std::vec::Vec::new();")?;
+    writeln!(out, "    let mut node = 0;")?;
+    writeln!(out, "    loop {{")?;
+    writeln!(out, "        node = match node {{")?;
+    for &node in &nodes {
+        let (id, dp, cc) = node;
+        writeln!(out,
+                 "            // block {}, {},{}",
+                 id,
+                 direction_char(dp),
+                 direction_char(cc))?;
+        let exit = match program.block(id).exit(dp, cc) {
+            Some(exit) => exit,
+            None => {
+                writeln!(out, "            {} => break,", labels[&node])?;
+                continue;
+            }
+        };
+        let successors = program.successors(node);
+        if exit.through_white || exit.cmd == Command::Nop {
+            writeln!(out, "            {} => {},", labels[&node], labels[&successors[0]])?;
+            continue;
+        }
+        let cmd = rust_command(exit.cmd);
+        let pos = format!("Position {{ left: {}, top: {} }}", exit.from.left, exit.from.top);
+        writeln!(out, "            {} => {{", labels[&node])?;
+        let call = format!("execute({}, {}, {}, &mut stack, &mut input, output, \
+                            OverflowPolicy::{:?})?",
+                           cmd,
+                           program.block(id).size,
+                           pos,
+                           opts.overflow);
+        if successors.len() == 1 {
+            writeln!(out, "                {};", call)?;
+            writeln!(out, "                {}", labels[&successors[0]])?;
+        } else {
+            writeln!(out, "                match {} {{", call)?;
+            for (turns, to) in successors.iter().enumerate() {
+                if turns + 1 == successors.len() {
+                    writeln!(out, "                    _ => {},", labels[to])?;
+                } else {
+                    writeln!(out, "                    {} => {},", turns, labels[to])?;
+                }
+            }
+            writeln!(out, "                }}")?;
+        }
+        writeln!(out, "            }}")?;
+    }
+    writeln!(out, "            _ => unreachable!(),")?;
+    writeln!(out, "        }};")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "    output.flush()?;")?;
+    writeln!(out, "    Ok(())")?;
+    writeln!(out, "}}")
+}
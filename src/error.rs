@@ -0,0 +1,87 @@
+//! Errors that can occur while loading or running a piet program
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use image::ImageError;
+
+use command::Command;
+use state::{Position, State};
+
+#[derive(Debug)]
+pub enum PietError {
+    /// The image could not be opened or decoded
+    ImageLoad(ImageError),
+    /// The codel size is zero, does not evenly divide the image dimensions or splits the image
+    /// into squares that are not a single color
+    InvalidCodelSize { size: u32, width: u32, height: u32 },
+    /// A color that is not one of the 20 piet colors. The position is that of the offending
+    /// codel, when known.
+    InvalidColor { rgb: (u8, u8, u8), pos: Option<Position> },
+    /// An arithmetic command overflowed with `OverflowPolicy::Abort`
+    Overflow { cmd: Command, pos: Position },
+    /// Reading program input or writing program output failed
+    Io(io::Error),
+    /// The program executed more steps than allowed. `state` is the state it was stopped in.
+    StepLimitExceeded { limit: u64, state: State },
+    /// The program ran for longer than allowed
+    Timeout { limit: Duration, state: State },
+    /// The stack grew larger than allowed
+    StackLimitExceeded { limit: usize, state: State },
+}
+
+impl fmt::Display for PietError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PietError::ImageLoad(ref e) => write!(f, "Could not open image: {}", e),
+            PietError::InvalidCodelSize { size, width, height } => {
+                write!(f,
+                       "Invalid codel size {} for an image of {}x{}",
+                       size,
+                       width,
+                       height)
+            }
+            PietError::InvalidColor { rgb, pos: Some(pos) } => {
+                write!(f, "Invalid color {:?} at {}", rgb, pos)
+            }
+            PietError::InvalidColor { rgb, pos: None } => write!(f, "Invalid color {:?}", rgb),
+            PietError::Overflow { cmd, pos } => {
+                write!(f, "Arithmetic overflow in {:?} at {}", cmd, pos)
+            }
+            PietError::Io(ref e) => write!(f, "I/O error: {}", e),
+            PietError::StepLimitExceeded { limit, ref state } => {
+                write!(f, "Exceeded the limit of {} steps at {}", limit, state)
+            }
+            PietError::Timeout { limit, ref state } => {
+                write!(f, "Exceeded the time limit of {:?} at {}", limit, state)
+            }
+            PietError::StackLimitExceeded { limit, ref state } => {
+                write!(f, "Exceeded the stack limit of {} values at {}", limit, state)
+            }
+        }
+    }
+}
+
+impl Error for PietError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            PietError::ImageLoad(ref e) => Some(e),
+            PietError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PietError {
+    fn from(err: io::Error) -> PietError {
+        PietError::Io(err)
+    }
+}
+
+impl From<ImageError> for PietError {
+    fn from(err: ImageError) -> PietError {
+        PietError::ImageLoad(err)
+    }
+}
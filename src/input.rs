@@ -0,0 +1,138 @@
+//! Parses values for the `In` commands out of a buffered input stream, leaving anything that
+//! is not part of the value unread for the next `In`.
+
+use std::io::{self, BufRead, Read};
+
+use value::Value;
+
+/// Wraps a reader so that bytes read from it can be put back, to be read again before the rest.
+/// This lets `read_number` leave input unread when it had to look further ahead than `R`
+/// buffers.
+#[derive(Debug)]
+pub struct Pushback<R> {
+    inner: R,
+    /// Bytes put back, the next one last
+    unread: Vec<u8>,
+}
+
+impl<R> Pushback<R> {
+    pub fn new(inner: R) -> Pushback<R> {
+        Pushback {
+            inner,
+            unread: Vec::new(),
+        }
+    }
+
+    /// Returns the wrapped reader. Any bytes put back are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Puts `bytes` back in front of the input.
+    pub fn unread(&mut self, bytes: &[u8]) {
+        self.unread.extend(bytes.iter().rev());
+    }
+}
+
+impl<R: BufRead> Read for Pushback<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Pushback<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if !self.unread.is_empty() {
+            let len = self.unread.len();
+            return Ok(&self.unread[len - 1..]);
+        }
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.unread.is_empty() {
+            self.inner.consume(amt);
+        } else {
+            let len = self.unread.len();
+            self.unread.truncate(len - amt);
+        }
+    }
+}
+
+fn peek_byte<R: BufRead>(input: &mut R) -> io::Result<Option<u8>> {
+    loop {
+        match input.fill_buf() {
+            Ok(buf) => return Ok(buf.first().cloned()),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Reads a single UTF-8 encoded character. Invalid sequences are consumed one byte at a time
+/// and decoded as U+FFFD. Returns `None` at end of input.
+pub fn read_char<R: BufRead>(input: &mut R) -> io::Result<Option<char>> {
+    let first = match peek_byte(input)? {
+        Some(b) => b,
+        None => return Ok(None),
+    };
+    input.consume(1);
+    let len = match first {
+        0x00..=0x7f => return Ok(Some(first as char)),
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Some(::std::char::REPLACEMENT_CHARACTER)),
+    };
+
+    let mut bytes = vec![first];
+    while bytes.len() < len {
+        match peek_byte(input)? {
+            Some(b) if b & 0xc0 == 0x80 => {
+                input.consume(1);
+                bytes.push(b);
+            }
+            _ => break,
+        }
+    }
+    Ok(Some(::std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.chars().next())
+        .unwrap_or(::std::char::REPLACEMENT_CHARACTER)))
+}
+
+/// Reads a signed decimal integer, skipping any leading ASCII whitespace. Returns `None` if the
+/// input ends or does not continue with a number, or if the number does not fit in a `Value`.
+/// In that case whatever was read after the whitespace is put back.
+pub fn read_number<R: BufRead>(input: &mut Pushback<R>) -> io::Result<Option<Value>> {
+    while let Some(b) = peek_byte(input)? {
+        if !b.is_ascii_whitespace() {
+            break;
+        }
+        input.consume(1);
+    }
+
+    let mut number = String::new();
+    while let Some(b) = peek_byte(input)? {
+        let sign = number.is_empty() && (b == b'-' || b == b'+');
+        if !sign && !b.is_ascii_digit() {
+            break;
+        }
+        input.consume(1);
+        number.push(b as char);
+    }
+    match number.parse() {
+        Ok(n) => Ok(Some(n)),
+        Err(_) => {
+            input.unread(number.as_bytes());
+            Ok(None)
+        }
+    }
+}
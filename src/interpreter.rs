@@ -0,0 +1,465 @@
+//! Executes a piet program one color block at a time
+
+use std::convert::TryFrom;
+use std::io::{self, BufRead, StdinLock, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use image::RgbImage;
+
+use color::Color;
+use command::{Command, DataType};
+use error::PietError;
+use input::{self, Pushback};
+use program::{Block, Exit, Program};
+use state::{State, Position, Direction};
+use value::{OverflowPolicy, StackValue, Value};
+
+macro_rules! get {
+    ($e: expr) => (match $e { Some(e) => e, None => return Ok(0)});
+}
+
+/// A single transition from one color block into the next, as yielded by `Interpreter`.
+///
+/// Transitions into or out of a white block execute no command and are reported as
+/// `Command::Nop`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub cmd: Command,
+    pub from_pos: Position,
+    pub to_pos: Position,
+    pub from_color: Color,
+    pub to_color: Color,
+    pub dp_before: Direction,
+    pub cc_before: Direction,
+    pub dp_after: Direction,
+    pub cc_after: Direction,
+    /// Values removed from the top of the stack, bottom-most first.
+    pub popped: Vec<Value>,
+    /// Values added to the top of the stack in their place, bottom-most first.
+    pub pushed: Vec<Value>,
+}
+
+/// Runs a piet program, yielding a `Step` for every transition between color blocks until the
+/// program terminates or fails. Failures are available through `Interpreter::error`.
+///
+/// `In` commands read from `R` and `Out` commands write to `W`, which default to the process'
+/// stdin and stdout.
+pub struct Interpreter<R = StdinLock<'static>, W = Stdout> {
+    program: Program,
+    state: State,
+    input: Pushback<R>,
+    output: W,
+    overflow_policy: OverflowPolicy,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    max_stack: Option<usize>,
+    steps: u64,
+    started: Option<Instant>,
+    finished: bool,
+    error: Option<PietError>,
+}
+
+impl Interpreter {
+    /// Creates an interpreter for a program whose pixels are already one codel each, as
+    /// returned by `reader::read`, reading from stdin and writing to stdout. Fails if any codel
+    /// is not one of the piet colors.
+    pub fn new(img: RgbImage) -> Result<Interpreter, PietError> {
+        Interpreter::with_io(img, io::stdin().lock(), io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    /// Creates an interpreter which reads program input from `input` and writes program
+    /// output to `output`.
+    pub fn with_io(img: RgbImage, input: R, output: W) -> Result<Interpreter<R, W>, PietError> {
+        Ok(Interpreter {
+            program: Program::new(img)?,
+            state: State::new(),
+            input: Pushback::new(input),
+            output,
+            overflow_policy: OverflowPolicy::default(),
+            max_steps: None,
+            timeout: None,
+            max_stack: None,
+            steps: 0,
+            started: None,
+            finished: false,
+            error: None,
+        })
+    }
+
+    /// Sets how `Add`, `Subtract` and `Multiply` behave when their result overflows.
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow_policy = policy;
+    }
+
+    /// Stops the program with `PietError::StepLimitExceeded` once it has executed `limit` steps.
+    pub fn set_max_steps(&mut self, limit: Option<u64>) {
+        self.max_steps = limit;
+    }
+
+    /// Stops the program with `PietError::Timeout` once `limit` has passed since its first step.
+    /// Time spent blocked on input counts, but is not interrupted.
+    pub fn set_timeout(&mut self, limit: Option<Duration>) {
+        self.timeout = limit;
+    }
+
+    /// Stops the program with `PietError::StackLimitExceeded` once its stack holds more than
+    /// `limit` values.
+    pub fn set_max_stack(&mut self, limit: Option<usize>) {
+        self.max_stack = limit;
+    }
+
+    /// The number of steps executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Consumes the interpreter, returning its input and output streams. Input that an `In`
+    /// read ahead and put back is lost.
+    pub fn into_io(self) -> (R, W) {
+        (self.input.into_inner(), self.output)
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn image(&self) -> &RgbImage {
+        self.program.image()
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The error that stopped iteration, if any.
+    pub fn error(&self) -> Option<&PietError> {
+        self.error.as_ref()
+    }
+
+    /// The command the next step will execute, or `None` if the program has terminated or
+    /// will terminate instead of taking another step.
+    pub fn next_command(&self) -> Option<Command> {
+        if self.finished {
+            return None;
+        }
+        let state = &self.state;
+        self.program.exit_from(state.pos, state.dp(), state.cc()).map(|exit| exit.cmd)
+    }
+
+    /// Runs the program until it terminates.
+    pub fn run(&mut self) -> Result<(), PietError> {
+        while self.step()?.is_some() {}
+        Ok(())
+    }
+
+    /// Moves into the next color block, executing the command that transition encodes.
+    /// Returns `None` once the program has terminated.
+    pub fn step(&mut self) -> Result<Option<Step>, PietError> {
+        if self.finished {
+            return Ok(None);
+        }
+        let program = &self.program;
+        let state = &mut self.state;
+        let (dp_before, cc_before) = (state.dp(), state.cc());
+        let exit = match program.exit_from(state.pos, dp_before, cc_before) {
+            Some(exit) => exit,
+            None => {
+                info!("END OF PROGRAM!");
+                return self.finish();
+            }
+        };
+        if let Some(limit) = self.max_steps {
+            if self.steps >= limit {
+                return Err(PietError::StepLimitExceeded {
+                    limit,
+                    state: state.clone(),
+                });
+            }
+        }
+        if let Some(limit) = self.timeout {
+            if self.started.get_or_insert_with(Instant::now).elapsed() > limit {
+                return Err(PietError::Timeout {
+                    limit,
+                    state: state.clone(),
+                });
+            }
+        }
+        let from_blk = program.block_at(state.pos);
+        let from_color = from_blk.color;
+        let to_color = program.block_at(exit.to).color;
+        let seen_white = exit.through_white;
+        let last_pos = exit.from;
+
+        let cmd = exit.cmd;
+        if seen_white {
+            debug!("Seen white: not executing command");
+        }
+        let base = state.stack.len() - touched_depth(cmd, &state.stack);
+        let before = state.stack[base..].to_vec();
+        // The command runs before the state moves on, so that a failed step can be retried
+        let turns = if seen_white {
+            0
+        } else {
+            exec_cmd(&exit,
+                     &from_color,
+                     &to_color,
+                     from_blk,
+                     &mut state.stack,
+                     &mut self.input,
+                     &mut self.output,
+                     self.overflow_policy)?
+        };
+        state.pos = exit.to;
+        state.set_dp(exit.dp_after);
+        state.set_cc(exit.cc_after);
+        match cmd {
+            Command::Pointer => {
+                for _ in 0..turns {
+                    state.rot_clockwise();
+                }
+            }
+            Command::Switch if turns == 1 => state.toggle_cc(),
+            _ => (),
+        }
+        trace!("STACK: {:?}", state.stack);
+        self.steps += 1;
+        if let Some(limit) = self.max_stack {
+            if state.stack.len() > limit {
+                return Err(PietError::StackLimitExceeded {
+                    limit,
+                    state: state.clone(),
+                });
+            }
+        }
+        let after = &state.stack[base.min(state.stack.len())..];
+        let unchanged = before.iter().zip(after).take_while(|&(b, a)| b == a).count();
+
+        Ok(Some(Step {
+            cmd,
+            from_pos: last_pos,
+            to_pos: state.pos,
+            from_color,
+            to_color,
+            dp_before,
+            cc_before,
+            dp_after: state.dp(),
+            cc_after: state.cc(),
+            popped: before[unchanged..].to_vec(),
+            pushed: after[unchanged..].to_vec(),
+        }))
+    }
+
+    fn finish(&mut self) -> Result<Option<Step>, PietError> {
+        self.finished = true;
+        self.output.flush()?;
+        Ok(None)
+    }
+}
+
+impl<R: BufRead, W: Write> Iterator for Interpreter<R, W> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        match self.step() {
+            Ok(step) => step,
+            Err(e) => {
+                self.finished = true;
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+/// How many values `cmd` needs on the stack to be executed.
+fn required_depth(cmd: Command) -> usize {
+    match cmd {
+        Command::Nop | Command::Push | Command::In(_) => 0,
+        Command::Pop | Command::Not | Command::Pointer | Command::Switch |
+        Command::Duplicate | Command::Out(_) => 1,
+        Command::Add | Command::Subtract | Command::Multiply | Command::Divide |
+        Command::Mod | Command::Greater | Command::Roll => 2,
+    }
+}
+
+/// How many values at the top of the stack `cmd` may inspect or change.
+fn touched_depth(cmd: Command, stack: &[Value]) -> usize {
+    let depth = match cmd {
+        Command::Roll => {
+            let roll_depth = match stack.len() {
+                len if len >= 2 => stack[len - 2].to_i64().map_or(0, |n| n.max(0) as usize),
+                _ => 0,
+            };
+            roll_depth.saturating_add(2)
+        }
+        _ => required_depth(cmd),
+    };
+    depth.min(stack.len())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn exec_cmd<R: BufRead, W: Write>(exit: &Exit,
+                                  from_color: &Color,
+                                  to_color: &Color,
+                                  from_blk: &Block,
+                                  stack: &mut Vec<Value>,
+                                  input: &mut Pushback<R>,
+                                  output: &mut W,
+                                  overflow: OverflowPolicy)
+                                  -> Result<usize, PietError> {
+    info!("exec_cmd: {:?} -- {} @ {} --> {} @ {} (DP: {:?}, CC: {:?})",
+          exit.cmd,
+          from_color,
+          exit.from,
+          to_color,
+          exit.to,
+          exit.dp_after,
+          exit.cc_after);
+    execute(exit.cmd, from_blk.size, exit.from, stack, input, output, overflow)
+}
+
+/// Executes `cmd` on `stack` as a step out of a block of `size` codels through `pos`. Returns
+/// how many times `Pointer` rotates the DP clockwise or `Switch` toggles the CC, and 0 for
+/// other commands. A command the stack is too small for, or whose result is undefined, leaves
+/// the stack untouched, except for `Out(Char)`, which pops and drops a value that is not a
+/// character.
+pub fn execute<R: BufRead, W: Write>(cmd: Command,
+                                     size: usize,
+                                     pos: Position,
+                                     stack: &mut Vec<Value>,
+                                     input: &mut Pushback<R>,
+                                     output: &mut W,
+                                     overflow: OverflowPolicy)
+                                     -> Result<usize, PietError> {
+    match cmd {
+        Command::Nop => (),
+        Command::Push => {
+            stack.push(Value::from_i64(size as i64));
+        }
+        Command::Pop => {
+            stack.pop();
+        }
+        Command::Add | Command::Subtract | Command::Multiply => {
+            let mut overflowed = false;
+            binary_op(stack, |lhs, rhs| {
+                let res = match cmd {
+                    Command::Add => lhs.try_add(rhs),
+                    Command::Subtract => lhs.try_sub(rhs),
+                    _ => lhs.try_mul(rhs),
+                };
+                if res.is_some() {
+                    return res;
+                }
+                overflowed = true;
+                match (overflow, cmd) {
+                    (OverflowPolicy::Wrap, Command::Add) => Some(lhs.wrapped_add(rhs)),
+                    (OverflowPolicy::Wrap, Command::Subtract) => Some(lhs.wrapped_sub(rhs)),
+                    (OverflowPolicy::Wrap, _) => Some(lhs.wrapped_mul(rhs)),
+                    (OverflowPolicy::Saturate, Command::Add) => Some(lhs.saturated_add(rhs)),
+                    (OverflowPolicy::Saturate, Command::Subtract) => Some(lhs.saturated_sub(rhs)),
+                    (OverflowPolicy::Saturate, _) => Some(lhs.saturated_mul(rhs)),
+                    (OverflowPolicy::Ignore, _) |
+                    (OverflowPolicy::Abort, _) => None,
+                }
+            });
+            if overflowed && overflow == OverflowPolicy::Abort {
+                return Err(PietError::Overflow { cmd, pos });
+            }
+        }
+        Command::Divide => binary_op(stack, |lhs, rhs| lhs.try_div(rhs)),
+        Command::Mod => binary_op(stack, |lhs, rhs| lhs.try_mod(rhs)),
+        Command::Not => {
+            let top = get!(stack.pop());
+            stack.push(Value::from_i64(if top.is_zero() { 1 } else { 0 }));
+        }
+        Command::Greater => {
+            binary_op(stack,
+                      |lhs, rhs| Some(Value::from_i64(if lhs > rhs { 1 } else { 0 })))
+        }
+        Command::Pointer => {
+            let top = get!(stack.pop());
+            return Ok(get!(top.try_mod(&Value::from_i64(4)).and_then(|n| n.to_i64())) as usize);
+        }
+        Command::Switch => {
+            let top = get!(stack.pop());
+            if !get!(top.try_mod(&Value::from_i64(2))).is_zero() {
+                return Ok(1);
+            }
+        }
+        Command::Duplicate => {
+            let last = get!(stack.last().cloned());
+            stack.push(last);
+        }
+        Command::Roll => {
+            let len = stack.len();
+            if len < 2 {
+                return Ok(0);
+            }
+            let depth = get!(stack[len - 2].to_i64());
+            trace!("ROLL: {}, {}, {}", stack[len - 1], depth, len);
+            trace!("ROLL STACK: {:?}", stack);
+            // A negative depth is an error and a depth of zero has no effect
+            if depth <= 0 || depth > (len - 2) as i64 {
+                return Ok(0);
+            }
+            let rolls = get!(stack[len - 1].try_mod(&stack[len - 2]).and_then(|n| n.to_i64()));
+            stack.truncate(len - 2);
+            let start = stack.len() - depth as usize;
+            stack[start..].rotate_right(rolls as usize);
+        }
+        Command::In(dtype) => {
+            output.flush()?;
+            let value = match dtype {
+                DataType::Number => input::read_number(input),
+                DataType::Char => {
+                    input::read_char(input).map(|c| c.map(|c| Value::from_i64(i64::from(c as u32))))
+                }
+            };
+            let value = value?;
+            stack.push(get!(value));
+        }
+        Command::Out(dtype) => {
+            let value = get!(stack.pop());
+            let written = match dtype {
+                DataType::Number => write!(output, "{}", value),
+                DataType::Char => {
+                    let outchar = get!(value.to_i64()
+                        .and_then(|n| u32::try_from(n).ok())
+                        .and_then(::std::char::from_u32));
+                    write!(output, "{}", outchar)
+                }
+            };
+            written?;
+        }
+    }
+    Ok(0)
+}
+
+/// Applies `op` to the second and top values of the stack, replacing them with its result.
+/// If the stack is too small or `op` has no result, the stack is left untouched.
+fn binary_op<F>(stack: &mut Vec<Value>, op: F)
+    where F: FnOnce(&Value, &Value) -> Option<Value>
+{
+    let len = stack.len();
+    if len < 2 {
+        return;
+    }
+    if let Some(res) = op(&stack[len - 2], &stack[len - 1]) {
+        stack.truncate(len - 2);
+        stack.push(res);
+    }
+}
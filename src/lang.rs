@@ -0,0 +1,530 @@
+//! A small structured language that compiles to piet programs.
+//!
+//! ```text
+//! # Prints the first ten fibonacci numbers
+//! let a = 0;
+//! let b = 1;
+//! let n = 10;
+//! while n > 0 {
+//!     print a;
+//!     print " ";
+//!     let t = a + b;
+//!     a = b;
+//!     b = t;
+//!     n = n - 1;
+//! }
+//! ```
+//!
+//! Values are integers, with `'a'` style character literals. Expressions support `+ - * / %`,
+//! comparisons, `!`, `&&` and `||`, with conditions and logical operators treating any value
+//! other than 0 as true. Statements are `let x = e;` to declare a variable, `x = e;`, `print e;`
+//! and `printc e;` to write a number or character, `print "text";`, `input x;` and `inputc x;`
+//! to read a number or character, `if e { .. } else { .. }` and `while e { .. }`.
+//!
+//! Variables live at the bottom of the stack, each in the slot of its declaration, and are
+//! reached with `roll`. A variable may only be used after its declaration, but is never freed:
+//! declaring one within a loop reuses the same slot on every iteration. Reading input once it
+//! has run out, or dividing by zero, leaves the stack in a state the program doesn't expect.
+
+use asm::Instruction;
+use command::{Command, DataType};
+use layout::Piece;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+/// Variables are referred to by the index of their declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Num(i64),
+    Var(usize),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    /// Sets a variable, whether it is being declared or not
+    Assign(usize, Expr),
+    Print(Expr),
+    PrintChar(Expr),
+    PrintStr(String),
+    Input(usize, DataType),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    /// The name of every variable, in the order they are declared
+    pub vars: Vec<String>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Num(i64),
+    Str(String),
+    /// Punctuation and operators
+    Sym(&'static str),
+}
+
+static SYMBOLS: [&str; 20] = ["==", "!=", "<=", ">=", "&&", "||", "{", "}", "(", ")", ";", "=",
+                              "<", ">", "+", "-", "*", "/", "%", "!"];
+
+static KEYWORDS: [&str; 8] = ["let", "print", "printc", "input", "inputc", "if", "else", "while"];
+
+fn unescape(c: char) -> Option<char> {
+    match c {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        '0' => Some('\0'),
+        '\\' | '\'' | '"' => Some(c),
+        _ => None,
+    }
+}
+
+/// Splits `src` into tokens, each paired with the line it is on.
+fn tokenize(src: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let lineno = i + 1;
+        let err = |msg: String| format!("line {}: {}", lineno, msg);
+        let mut chars = line.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if c == '#' {
+                break;
+            } else if c.is_whitespace() {
+                chars.next();
+            } else if c.is_ascii_digit() || c.is_alphabetic() || c == '_' {
+                let mut end = start;
+                while let Some(&(j, c)) = chars.peek() {
+                    if !c.is_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    end = j + c.len_utf8();
+                    chars.next();
+                }
+                let word = &line[start..end];
+                tokens.push((if c.is_ascii_digit() {
+                                 Token::Num(word.parse()
+                                     .map_err(|_| err(format!("Invalid number: {}", word)))?)
+                             } else {
+                                 Token::Ident(word.to_string())
+                             },
+                             lineno));
+            } else if c == '"' || c == '\'' {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, '\\')) => {
+                            let escaped = chars.next().map(|(_, e)| e);
+                            text.push(escaped.and_then(unescape)
+                                .ok_or_else(|| err("Invalid escape sequence".to_string()))?);
+                        }
+                        Some((_, other)) => text.push(other),
+                        None => return Err(err("Unterminated literal".to_string())),
+                    }
+                }
+                if c == '"' {
+                    tokens.push((Token::Str(text), lineno));
+                } else {
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(ch), None) => tokens.push((Token::Num(ch as i64), lineno)),
+                        _ => return Err(err(format!("Invalid character literal: '{}'", text))),
+                    }
+                }
+            } else {
+                let sym = SYMBOLS.iter()
+                    .find(|sym| line[start..].starts_with(**sym))
+                    .ok_or_else(|| err(format!("Unexpected character: {}", c)))?;
+                for _ in 0..sym.len() {
+                    chars.next();
+                }
+                tokens.push((Token::Sym(sym), lineno));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    vars: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn error(&self, msg: &str) -> String {
+        match self.tokens.get(self.pos) {
+            Some(&(_, line)) => format!("line {}: {}", line, msg),
+            None => format!("at end of input: {}", msg),
+        }
+    }
+
+    fn is_sym(&self, sym: &str) -> bool {
+        match self.peek() {
+            Some(&Token::Sym(found)) => found == sym,
+            Some(Token::Ident(word)) => KEYWORDS.contains(&sym) && word == sym,
+            _ => false,
+        }
+    }
+
+    /// Consumes `sym`, which may be a symbol or a keyword, if it is next.
+    fn eat(&mut self, sym: &str) -> bool {
+        let found = self.is_sym(sym);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, sym: &str) -> Result<(), String> {
+        if self.eat(sym) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected `{}`", sym)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Token::Ident(ref name)) if KEYWORDS.contains(&name.as_str()) => {
+                Err(self.error(&format!("Expected a variable name, found `{}`", name)))
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("Expected a variable name")),
+        }
+    }
+
+    /// Parses the name of a variable that must already be declared.
+    fn var(&mut self) -> Result<usize, String> {
+        let pos = self.pos;
+        let name = self.ident()?;
+        self.vars.iter().position(|var| *var == name).ok_or_else(|| {
+            self.pos = pos;
+            self.error(&format!("Undeclared variable: {}", name))
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            if self.peek().is_none() {
+                return Err(self.error("Expected `}`"));
+            }
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, String> {
+        let stmt = if self.eat("let") {
+            let pos = self.pos;
+            let name = self.ident()?;
+            self.expect("=")?;
+            let value = self.expr()?;
+            if self.vars.contains(&name) {
+                self.pos = pos;
+                return Err(self.error(&format!("Variable declared twice: {}", name)));
+            }
+            self.vars.push(name);
+            Stmt::Assign(self.vars.len() - 1, value)
+        } else if self.eat("print") {
+            match self.peek().cloned() {
+                Some(Token::Str(text)) => {
+                    self.pos += 1;
+                    Stmt::PrintStr(text)
+                }
+                _ => Stmt::Print(self.expr()?),
+            }
+        } else if self.eat("printc") {
+            Stmt::PrintChar(self.expr()?)
+        } else if self.eat("input") {
+            Stmt::Input(self.var()?, DataType::Number)
+        } else if self.eat("inputc") {
+            Stmt::Input(self.var()?, DataType::Char)
+        } else if self.eat("if") {
+            let cond = self.expr()?;
+            let then = self.block()?;
+            let els = if !self.eat("else") {
+                Vec::new()
+            } else if self.is_sym("if") {
+                vec![self.stmt()?]
+            } else {
+                self.block()?
+            };
+            return Ok(Stmt::If(cond, then, els));
+        } else if self.eat("while") {
+            let cond = self.expr()?;
+            return Ok(Stmt::While(cond, self.block()?));
+        } else {
+            let var = self.var()?;
+            self.expect("=")?;
+            Stmt::Assign(var, self.expr()?)
+        };
+        self.expect(";")?;
+        Ok(stmt)
+    }
+
+    /// Parses a left associative chain of `ops`, whose operands are parsed by `operand`.
+    fn binary<F>(&mut self, ops: &[(&str, BinOp)], operand: F) -> Result<Expr, String>
+        where F: Fn(&mut Parser) -> Result<Expr, String>
+    {
+        let mut lhs = operand(self)?;
+        while let Some(&(_, op)) = ops.iter().find(|&&(sym, _)| self.is_sym(sym)) {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(operand(self)?));
+        }
+        Ok(lhs)
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.binary(&[("||", BinOp::Or)], |p| p.binary(&[("&&", BinOp::And)], Parser::comparison))
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let ops = [("==", BinOp::Eq),
+                   ("!=", BinOp::Ne),
+                   ("<=", BinOp::Le),
+                   (">=", BinOp::Ge),
+                   ("<", BinOp::Lt),
+                   (">", BinOp::Gt)];
+        let additive = |p: &mut Parser| {
+            p.binary(&[("+", BinOp::Add), ("-", BinOp::Sub)], |p| {
+                p.binary(&[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Mod)],
+                         Parser::unary)
+            })
+        };
+        let lhs = additive(self)?;
+        match ops.iter().find(|&&(sym, _)| self.is_sym(sym)) {
+            Some(&(_, op)) => {
+                self.pos += 1;
+                Ok(Expr::Binary(op, Box::new(lhs), Box::new(additive(self)?)))
+            }
+            None => Ok(lhs),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            Ok(Expr::Unary(UnOp::Neg, Box::new(self.unary()?)))
+        } else if self.eat("!") {
+            Ok(Expr::Unary(UnOp::Not, Box::new(self.unary()?)))
+        } else if self.eat("(") {
+            let expr = self.expr()?;
+            self.expect(")")?;
+            Ok(expr)
+        } else if let Some(Token::Num(n)) = self.peek().cloned() {
+            self.pos += 1;
+            Ok(Expr::Num(n))
+        } else {
+            Ok(Expr::Var(self.var()?))
+        }
+    }
+}
+
+/// Parses a script. Errors include the line number they occurred on.
+pub fn parse(src: &str) -> Result<Script, String> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+        vars: Vec::new(),
+    };
+    let mut body = Vec::new();
+    while parser.peek().is_some() {
+        body.push(parser.stmt()?);
+    }
+    Ok(Script {
+        vars: parser.vars,
+        body,
+    })
+}
+
+fn op(cmd: Command) -> Instruction {
+    Instruction::Op(cmd)
+}
+
+struct Codegen {
+    /// The number of variables at the bottom of the stack
+    vars: usize,
+}
+
+impl Codegen {
+    /// Copies the value `depth` values down from the top of the stack onto the top.
+    fn copy(&self, depth: usize, out: &mut Vec<Instruction>) {
+        if depth == 0 {
+            out.push(op(Command::Duplicate));
+            return;
+        }
+        // Bring the value up, duplicate it, and bury the original again
+        out.extend_from_slice(&[Instruction::Push(depth as i64 + 1),
+                                Instruction::Push(depth as i64),
+                                op(Command::Roll),
+                                op(Command::Duplicate),
+                                Instruction::Push(depth as i64 + 2),
+                                Instruction::Push(1),
+                                op(Command::Roll)]);
+    }
+
+    /// Moves the value on top of the stack into the slot of `var`.
+    fn store(&self, var: usize, out: &mut Vec<Instruction>) {
+        let depth = (self.vars - var) as i64;
+        // Bring the old value up, drop it, and bury the new value in its place
+        out.extend_from_slice(&[Instruction::Push(depth + 1),
+                                Instruction::Push(depth),
+                                op(Command::Roll),
+                                op(Command::Pop)]);
+        if depth > 1 {
+            out.extend_from_slice(&[Instruction::Push(depth),
+                                    Instruction::Push(1),
+                                    op(Command::Roll)]);
+        }
+    }
+
+    /// Evaluates `expr` onto the top of the stack, above `temps` other temporary values.
+    fn expr(&self, expr: &Expr, temps: usize, out: &mut Vec<Instruction>) {
+        match *expr {
+            Expr::Num(n) => out.push(Instruction::Push(n)),
+            Expr::Var(var) => self.copy(self.vars - 1 - var + temps, out),
+            Expr::Unary(UnOp::Neg, ref operand) => {
+                out.push(Instruction::Push(0));
+                self.expr(operand, temps + 1, out);
+                out.push(op(Command::Subtract));
+            }
+            Expr::Unary(UnOp::Not, ref operand) => {
+                self.expr(operand, temps, out);
+                out.push(op(Command::Not));
+            }
+            Expr::Binary(binop, ref lhs, ref rhs) => {
+                // `greater` is the only comparison, so `<` and `>=` swap their operands
+                let (first, second) = match binop {
+                    BinOp::Lt | BinOp::Ge => (rhs, lhs),
+                    _ => (lhs, rhs),
+                };
+                self.expr(first, temps, out);
+                if binop == BinOp::And || binop == BinOp::Or {
+                    out.push(op(Command::Not));
+                }
+                self.expr(second, temps + 1, out);
+                let cmds: &[Command] = match binop {
+                    BinOp::Add => &[Command::Add],
+                    BinOp::Sub => &[Command::Subtract],
+                    BinOp::Mul => &[Command::Multiply],
+                    BinOp::Div => &[Command::Divide],
+                    BinOp::Mod => &[Command::Mod],
+                    BinOp::Eq => &[Command::Subtract, Command::Not],
+                    BinOp::Ne => &[Command::Subtract, Command::Not, Command::Not],
+                    BinOp::Gt | BinOp::Lt => &[Command::Greater],
+                    BinOp::Le | BinOp::Ge => &[Command::Greater, Command::Not],
+                    // Both not false, or not both false
+                    BinOp::And => &[Command::Not, Command::Add, Command::Not],
+                    BinOp::Or => &[Command::Not, Command::Multiply, Command::Not],
+                };
+                out.extend(cmds.iter().map(|&cmd| op(cmd)));
+            }
+        }
+    }
+
+    fn block(&self, stmts: &[Stmt]) -> Piece {
+        let mut pieces = Vec::new();
+        let mut strip = Vec::new();
+        for stmt in stmts {
+            match *stmt {
+                Stmt::Assign(var, ref value) => {
+                    self.expr(value, 0, &mut strip);
+                    self.store(var, &mut strip);
+                }
+                Stmt::Print(ref value) => {
+                    self.expr(value, 0, &mut strip);
+                    strip.push(op(Command::Out(DataType::Number)));
+                }
+                Stmt::PrintChar(ref value) => {
+                    self.expr(value, 0, &mut strip);
+                    strip.push(op(Command::Out(DataType::Char)));
+                }
+                Stmt::PrintStr(ref text) => {
+                    for c in text.chars() {
+                        strip.push(Instruction::Push(c as i64));
+                        strip.push(op(Command::Out(DataType::Char)));
+                    }
+                }
+                Stmt::Input(var, data_type) => {
+                    strip.push(op(Command::In(data_type)));
+                    self.store(var, &mut strip);
+                }
+                Stmt::If(ref cond, ref then, ref els) => {
+                    let mut cond_strip = Vec::new();
+                    self.expr(cond, 0, &mut cond_strip);
+                    if !strip.is_empty() {
+                        pieces.push(Piece::Strip(strip.split_off(0)));
+                    }
+                    pieces.push(Piece::If {
+                        cond: cond_strip,
+                        then: Box::new(self.block(then)),
+                        els: Box::new(self.block(els)),
+                    });
+                }
+                Stmt::While(ref cond, ref body) => {
+                    let mut cond_strip = Vec::new();
+                    self.expr(cond, 0, &mut cond_strip);
+                    if !strip.is_empty() {
+                        pieces.push(Piece::Strip(strip.split_off(0)));
+                    }
+                    pieces.push(Piece::While {
+                        cond: cond_strip,
+                        body: Box::new(self.block(body)),
+                    });
+                }
+            }
+        }
+        if !strip.is_empty() {
+            pieces.push(Piece::Strip(strip));
+        }
+        Piece::Seq(pieces)
+    }
+}
+
+/// Compiles a script into pieces that `layout::render` can draw. Every variable starts out as
+/// 0.
+pub fn compile(script: &Script) -> Piece {
+    let codegen = Codegen { vars: script.vars.len() };
+    let mut pieces = Vec::new();
+    if !script.vars.is_empty() {
+        pieces.push(Piece::Strip(vec![Instruction::Push(0); script.vars.len()]));
+    }
+    pieces.push(codegen.block(&script.body));
+    Piece::Seq(pieces)
+}
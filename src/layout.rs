@@ -0,0 +1,225 @@
+//! Lays out structured control flow as a piet program.
+//!
+//! A program is built from pieces, each a rectangle of codels that is entered at its top left
+//! codel moving right and left through its top right codel, which is always white. Everything
+//! else in a piece's last column is black, so that pieces can be placed side by side. The CC is
+//! never relied on, as slides through white and blocks with restricted exits toggle it.
+//!
+//! Commands run along the top row of a strip as in `asm`, except that every block ends in a
+//! single codel at its top right, so that it is left through the same codel whatever the CC.
+//! Branches execute `pointer` on a 0 or 1, which sends the interpreter either straight on or
+//! down a white column. Going down, it enters the middle of a three codel bar which has no
+//! exits other than to the right, turning it back to the right along a lower row:
+//!
+//! ```text
+//! C C P W . .    C: condition, P: the block pointer enters
+//! . . W . . .    W: white
+//! . T T T W .    T: the bar turning right
+//! ```
+//!
+//! An `If` continues with the `then` branch along the top row and the `else` branch along the
+//! lower one, and joins them in a column-high block, which both enter from white. A `While`
+//! runs its body along the lower row and then follows a white corridor right, down, left and
+//! up again, sliding around the piece clockwise back to the start of the condition.
+
+use image::{Rgb, RgbImage};
+
+use asm::{self, Instruction};
+use color::{Color, PIET_COLORS};
+use command::Command;
+
+const WHITE: (u8, u8, u8) = (255, 255, 255);
+const BLACK: (u8, u8, u8) = (0, 0, 0);
+
+/// The color strips start with, and that the blocks joining pieces are drawn in
+const START_COLOR: (u8, u8, u8) = PIET_COLORS[0];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Piece {
+    /// Commands executed one after another
+    Strip(Vec<Instruction>),
+    /// Pieces executed one after another
+    Seq(Vec<Piece>),
+    /// Executes `then` if `cond` leaves a value other than 0 on the stack, which is popped,
+    /// and `els` otherwise
+    If {
+        cond: Vec<Instruction>,
+        then: Box<Piece>,
+        els: Box<Piece>,
+    },
+    /// Executes `body` for as long as `cond` leaves a value other than 0 on the stack
+    While {
+        cond: Vec<Instruction>,
+        body: Box<Piece>,
+    },
+}
+
+/// A rectangle of codels, black until drawn on
+struct Grid {
+    width: u32,
+    height: u32,
+    cells: Vec<(u8, u8, u8)>,
+}
+
+impl Grid {
+    fn new(width: u32, height: u32) -> Grid {
+        Grid {
+            width,
+            height,
+            cells: vec![BLACK; width as usize * height as usize],
+        }
+    }
+
+    fn set(&mut self, x: u32, y: u32, rgb: (u8, u8, u8)) {
+        self.cells[y as usize * self.width as usize + x as usize] = rgb;
+    }
+
+    /// Copies `other` onto the grid with its top left codel at `(left, top)`.
+    fn blit(&mut self, other: &Grid, left: u32, top: u32) {
+        for y in 0..other.height {
+            for x in 0..other.width {
+                self.set(left + x, top + y, other.cells[(y * other.width + x) as usize]);
+            }
+        }
+    }
+
+    fn fill_row(&mut self, y: u32, from: u32, to: u32, rgb: (u8, u8, u8)) {
+        for x in from..to {
+            self.set(x, y, rgb);
+        }
+    }
+
+    fn fill_column(&mut self, x: u32, from: u32, to: u32, rgb: (u8, u8, u8)) {
+        for y in from..to {
+            self.set(x, y, rgb);
+        }
+    }
+}
+
+/// Draws a strip of commands, ending in a single codel block. That is followed by a white codel
+/// unless `open` is set, in which case the caller decides where the strip leads.
+fn strip(instrs: &[Instruction], open: bool) -> Grid {
+    let cmds = asm::lower(instrs);
+    let largest = cmds.iter().map(|&(size, _)| size).max().unwrap_or(1);
+    let height = (((largest - 1) as f64).sqrt().ceil() as u32).max(1);
+    let columns = |size: u64| {
+        if size == 1 { 1 } else { (size - 1).div_ceil(u64::from(height)) + 1 }
+    };
+    let width = cmds.iter().map(|&(size, _)| columns(size)).sum::<u64>() as u32 + 1;
+    let mut grid = Grid::new(if open { width } else { width + 1 }, height);
+
+    let mut color = Color::from_px(&START_COLOR).unwrap();
+    let mut left = 0;
+    for &(size, cmd) in &cmds {
+        // Fill columns top to bottom, then add the codel the block is left through
+        for i in 0..size - 1 {
+            grid.set(left + (i / u64::from(height)) as u32,
+                     (i % u64::from(height)) as u32,
+                     color.rgb_color);
+        }
+        left += columns(size) as u32;
+        grid.set(left - 1, 0, color.rgb_color);
+        let (hue_change, lightness_change) = cmd.color_change();
+        color = color.shifted(hue_change, lightness_change).unwrap();
+    }
+    grid.set(left, 0, color.rgb_color);
+    if !open {
+        grid.set(left + 1, 0, WHITE);
+    }
+    grid
+}
+
+/// Draws a condition ending in `pointer` at `(left, 0)` of `grid`, along with the white column
+/// and bar turning the interpreter right onto row `row` when the pointer turns it down.
+fn branch(grid: &mut Grid, cond: &Grid, left: u32, row: u32) {
+    grid.blit(cond, left, 0);
+    let p = left + cond.width - 1;
+    grid.fill_column(p, 1, row, WHITE);
+    grid.fill_row(row, p - 1, p + 2, START_COLOR);
+}
+
+fn draw(piece: &Piece) -> Grid {
+    match *piece {
+        Piece::Strip(ref instrs) => strip(instrs, false),
+        Piece::Seq(ref pieces) => {
+            let grids: Vec<Grid> = pieces.iter().map(draw).collect();
+            let mut seq = Grid::new(grids.iter().map(|grid| grid.width).sum(),
+                                    grids.iter().map(|grid| grid.height).max().unwrap_or(0));
+            let mut left = 0;
+            for grid in &grids {
+                seq.blit(grid, left, 0);
+                left += grid.width;
+            }
+            seq
+        }
+        Piece::If { ref cond, ref then, ref els } => {
+            // Zero means false, which the pointer should turn down into the else branch
+            let mut cond = cond.clone();
+            cond.extend_from_slice(&[Instruction::Op(Command::Not),
+                                     Instruction::Op(Command::Pointer)]);
+            let (cond, then, els) = (strip(&cond, true), draw(then), draw(els));
+            let row = cond.height.max(then.height).max(1) + 1;
+            let p = cond.width - 1;
+            // The column of the block joining the branches
+            let join = (p + 2 + then.width).max(p + 3 + els.width);
+            let mut grid = Grid::new(join + 3, row + els.height.max(1));
+
+            branch(&mut grid, &cond, 0, row);
+            grid.set(p + 1, 0, WHITE);
+            grid.blit(&then, p + 2, 0);
+            grid.fill_row(0, p + 2 + then.width, join, WHITE);
+            grid.set(p + 2, row, WHITE);
+            grid.blit(&els, p + 3, row);
+            grid.fill_row(row, p + 3 + els.width, join, WHITE);
+
+            grid.fill_column(join, 0, row + 1, START_COLOR);
+            grid.set(join + 1, 0, START_COLOR);
+            grid.set(join + 2, 0, WHITE);
+            grid
+        }
+        Piece::While { ref cond, ref body } => {
+            let mut cond = cond.clone();
+            cond.extend_from_slice(&[Instruction::Op(Command::Not),
+                                     Instruction::Op(Command::Not),
+                                     Instruction::Op(Command::Pointer)]);
+            let (cond, body) = (strip(&cond, true), draw(body));
+            let row = cond.height.max(1) + 1;
+            // The row below the body that the corridor leading back runs along, leaving a row
+            // of black so that no corridor within the body can slide into it
+            let bottom = row + body.height.max(1) + 1;
+            let p = cond.width;
+            // The column the corridor leading back runs down
+            let down = p + 3 + body.width;
+            let mut grid = Grid::new(down + 2, bottom + 1);
+
+            grid.fill_column(0, 0, bottom, WHITE);
+            branch(&mut grid, &cond, 1, row);
+            grid.set(p + 2, row, WHITE);
+            grid.blit(&body, p + 3, row);
+            grid.fill_column(down, row, bottom, WHITE);
+            grid.fill_row(bottom, 0, down + 1, WHITE);
+            grid.fill_row(0, p + 1, down + 2, WHITE);
+            grid
+        }
+    }
+}
+
+/// Draws `piece` as a program, with one pixel per codel, that terminates once the piece is
+/// done.
+pub fn render(piece: &Piece) -> RgbImage {
+    let program = draw(piece);
+    // Start in white if the piece is empty, so that the program still slides into the trap
+    let left = program.width.max(1);
+    let mut grid = Grid::new(left + 2, program.height.max(2));
+    grid.set(0, 0, WHITE);
+    grid.blit(&program, 0, 0);
+    // The same trap as `asm` ends programs in
+    grid.set(left, 0, WHITE);
+    for &(x, y) in &[(left + 1, 0), (left, 1), (left + 1, 1)] {
+        grid.set(x, y, START_COLOR);
+    }
+    RgbImage::from_fn(grid.width, grid.height, |x, y| {
+        let (r, g, b) = grid.cells[(y * grid.width + x) as usize];
+        Rgb([r, g, b])
+    })
+}
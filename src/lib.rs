@@ -0,0 +1,43 @@
+//! An interpreter for the [Piet](http://www.dangermouse.net/esoteric/piet.html) programming
+//! language.
+
+extern crate ansi_term;
+extern crate gif;
+extern crate image;
+#[macro_use]
+extern crate log;
+#[cfg(feature = "bigint")]
+extern crate num_bigint;
+#[cfg(feature = "bigint")]
+extern crate num_integer;
+#[cfg(feature = "bigint")]
+extern crate num_traits;
+
+pub mod asm;
+pub mod cfg;
+pub mod color;
+pub mod color_block;
+pub mod command;
+pub mod coverage;
+pub mod debugger;
+pub mod disasm;
+pub mod emit;
+pub mod error;
+pub mod input;
+pub mod interpreter;
+pub mod lang;
+pub mod layout;
+pub mod profile;
+pub mod program;
+pub mod reader;
+pub mod record;
+pub mod state;
+pub mod trace;
+pub mod util;
+pub mod value;
+pub mod visual;
+
+pub use command::{Command, DataType};
+pub use error::PietError;
+pub use interpreter::{Interpreter, Step};
+pub use value::{OverflowPolicy, StackValue, Value};
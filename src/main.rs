@@ -1,304 +1,460 @@
-// TODO: Handle white
-// TODO: Finish Commands
-// TODO: Refactor into interpreter iterator
-// TODO: Tests
-// TODO: Docs
-
-extern crate ansi_term;
-extern crate image;
-#[macro_use]
-extern crate log;
 extern crate env_logger;
+extern crate piet;
 
 use std::env;
-use std::io;
-use std::io::prelude::*;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::thread;
+use std::time::Duration;
 
-pub mod reader;
-pub mod state;
-pub mod color;
-pub mod color_block;
-pub mod util;
+use piet::{Interpreter, OverflowPolicy, PietError};
+use piet::asm;
+use piet::cfg::{self, Cfg};
+use piet::coverage::Coverage;
+use piet::debugger::Debugger;
+use piet::lang;
+use piet::layout;
+use piet::disasm;
+use piet::emit;
+use piet::profile::Profile;
+use piet::program::Program;
+use piet::reader;
+use piet::record::Recorder;
+use piet::trace;
+use piet::visual;
 
-use color_block::ColorBlock;
-use color::{Hue, Lightness};
-use state::{State, Position, Direction};
+/// Process exit codes for runs stopped by one of the execution limits
+const EXIT_STEP_LIMIT: i32 = 3;
+const EXIT_TIMEOUT: i32 = 4;
+const EXIT_STACK_LIMIT: i32 = 5;
 
-macro_rules! get {
-    ($e: expr) => (match $e { Some(e) => e, None => return ()});
-}
+/// How many steps are recorded when no step limit is given
+const DEFAULT_RECORDED_STEPS: u64 = 1000;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum DataType {
-    Number,
-    Char,
+enum Mode {
+    /// Run the program to completion
+    Run,
+    /// Step through the program in an interactive debugger
+    Debug,
+    /// Record the run as an animated GIF
+    Record,
+    /// List the blocks of the program and their exits without running it
+    Disasm,
+    /// Write the control flow graph of the program without running it
+    Cfg,
+    /// Compile the program into source code for another language
+    Compile,
+    /// Assemble a list of commands into a program
+    Asm,
+    /// Compile a script into a program
+    Build,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Command {
-    Nop,
-    Push,
-    Pop,
-    Add,
-    Subtract,
-    Multiply,
-    Divide,
-    Mod,
-    Not,
-    Greater,
-    Pointer,
-    Switch,
-    Duplicate,
-    Roll,
-    In(DataType),
-    Out(DataType),
+struct Options {
+    mode: Mode,
+    fname: String,
+    /// A file to read program input from instead of stdin
+    input: Option<String>,
+    read_opts: reader::Options,
+    overflow: OverflowPolicy,
+    /// Write an npiet style trace of every step to stderr
+    trace: bool,
+    /// Draw the program in the terminal as it runs
+    visual: bool,
+    /// How long to pause between steps in visual mode, or show each frame of a recording
+    delay: Duration,
+    /// Where to write a recording, control flow graph or assembled or compiled program
+    output: Option<String>,
+    /// The format to write the control flow graph in
+    format: cfg::Format,
+    /// The language to compile the program into
+    emit: emit::Target,
+    /// Show the stack next to the program in recordings
+    stack: bool,
+    /// Where to write an execution heatmap of the program
+    profile: Option<String>,
+    /// Where to write an image of the program with the blocks that were never entered dimmed
+    coverage: Option<String>,
+    /// How many pixels wide each codel is drawn in images, defaulting to 1 for heatmaps and
+    /// coverage and 8 for recordings
+    scale: Option<u32>,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    max_stack: Option<usize>,
 }
 
-fn would_hit_restriction(img: &image::RgbImage, state: &State) -> bool {
-    if state.dp() == Direction::Left && state.pos.left == 0 ||
-       state.dp() == Direction::Up && state.pos.top == 0 {
-        return true;
-    }
-
-    let nextpos = state.peek_pos();
-    if nextpos.left == img.width() || nextpos.top == img.height() {
-        return true;
+/// Splits `--flag=value` and `--flag value` style arguments into the flag and its value.
+fn flag_value<I>(arg: &str, args: &mut I) -> Result<(String, String), String>
+    where I: Iterator<Item = String>
+{
+    match arg.find('=') {
+        Some(i) => Ok((arg[..i].to_string(), arg[i + 1..].to_string())),
+        None => {
+            let value = args.next().ok_or_else(|| format!("{} requires a value", arg))?;
+            Ok((arg.to_string(), value))
+        }
     }
-    return util::get_px(&img, &nextpos) == (0, 0, 0);
 }
 
-fn exec_cmd(from_px: &(u8, u8, u8),
-            to_px: &(u8, u8, u8),
-            from_pos: Position,
-            to_pos: Position,
-            state: &mut State,
-            from_blk: &ColorBlock) {
-    static COMMAND_MATRIX: [[Command; 3]; 6] =
-        [[Command::Nop, Command::Push, Command::Pop],
-         [Command::Add, Command::Subtract, Command::Multiply],
-         [Command::Divide, Command::Mod, Command::Not],
-         [Command::Greater, Command::Pointer, Command::Switch],
-         [Command::Duplicate, Command::Roll, Command::In(DataType::Number)],
-         [Command::In(DataType::Char),
-          Command::Out(DataType::Number),
-          Command::Out(DataType::Char)]];
-    let from_color = color::Color::from_px(from_px).unwrap();
-    let to_color = color::Color::from_px(to_px).unwrap();
-    // FIXME: Change logic wrong
-    let hue_change = match (from_color.hue, to_color.hue) {
-        (Hue::Magenta, Hue::Red) => 1,
-        (from_hue, to_hue) => {
-            let (from_i, to_i) = (from_hue as i8, to_hue as i8);
-            if to_i < from_i {
-                6 - (from_i - to_i)
-            } else {
-                to_i - from_i
-            }
+fn parse_args() -> Result<Options, String> {
+    let mut mode = Mode::Run;
+    let mut fname = None;
+    let mut input = None;
+    let mut read_opts = reader::Options::default();
+    let mut overflow = OverflowPolicy::default();
+    let mut trace = false;
+    let mut visual = false;
+    let mut output = None;
+    let mut format = cfg::Format::default();
+    let mut emit = emit::Target::default();
+    let mut stack = false;
+    let mut delay = None;
+    let mut profile = None;
+    let mut coverage = None;
+    let mut scale = None;
+    let mut max_steps = None;
+    let mut timeout = None;
+    let mut max_stack = None;
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(|arg| arg.as_str()) {
+        Some("run") => {
+            args.next();
         }
-    };
-    let lightness_change = match (from_color.lightness, to_color.lightness) {
-        (Lightness::Dark, Lightness::Light) => 1,
-        (from_l, to_l) => {
-            let (from_i, to_i) = (from_l as i8, to_l as i8);
-            if to_i < from_i {
-                3 - (from_i - to_i)
-            } else {
-                to_i - from_i
-            }
+        Some("debug") => {
+            mode = Mode::Debug;
+            args.next();
         }
-    };
-    let cmd = COMMAND_MATRIX[hue_change as usize][lightness_change as usize];
-    info!("exec_cmd: {:?} -- {} @ {} --> {} @ {} (DP: {:?}, CC: {:?})",
-          cmd,
-          from_color,
-          from_pos,
-          to_color,
-          to_pos,
-          state.dp(),
-          state.cc());
-    match cmd {
-        Command::Nop => (),
-        Command::Push => {
-            state.stack.push(from_blk.value());
+        Some("record") => {
+            mode = Mode::Record;
+            args.next();
+        }
+        Some("disasm") => {
+            mode = Mode::Disasm;
+            args.next();
         }
-        Command::Pop => {
-            state.stack.pop();
+        Some("cfg") => {
+            mode = Mode::Cfg;
+            args.next();
         }
-        Command::Add => {
-            let lhs = get!(state.stack.pop());
-            let rhs = get!(state.stack.pop());
-            state.stack.push(lhs + rhs);
+        Some("compile") => {
+            mode = Mode::Compile;
+            args.next();
         }
-        Command::Subtract => {
-            let rhs = get!(state.stack.pop());
-            let lhs = get!(state.stack.pop());
-            state.stack.push(lhs - rhs);
+        Some("asm") => {
+            mode = Mode::Asm;
+            args.next();
         }
-        Command::Multiply => {
-            let lhs = get!(state.stack.pop());
-            let rhs = get!(state.stack.pop());
-            state.stack.push(lhs * rhs);
+        Some("build") => {
+            mode = Mode::Build;
+            args.next();
         }
-        Command::Divide | Command::Mod => {
-            let divisor = get!(state.stack.pop());
-            let dividend = get!(state.stack.pop());
-            if divisor == 0 {
-                return;
+        _ => (),
+    }
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            if fname.is_some() {
+                return Err(format!("Unexpected argument: {}", arg));
             }
-            let res = match cmd {
-                Command::Divide => dividend / divisor,
-                _ => dividend % divisor,
-            };
-            state.stack.push(res);
+            fname = Some(arg);
+            continue;
         }
-        Command::Not => {
-            let top = get!(state.stack.pop());
-            state.stack.push(if top == 0 { 1 } else { 0 });
+        if arg == "--trace" {
+            trace = true;
+            continue;
         }
-        Command::Greater => {
-            let first = get!(state.stack.pop());
-            let second = get!(state.stack.pop());
-            state.stack.push(if second > first { 1 } else { 0 });
+        if arg == "--visual" {
+            visual = true;
+            continue;
         }
-        Command::Pointer => {
-            const NUM_DIRECTIONS: i32 = 4;
-            let n_rotations = get!(state.stack.pop()) % NUM_DIRECTIONS;
-            let rotate_clockwise = n_rotations > 0;
-            for _ in 0..n_rotations.abs() {
-                if rotate_clockwise {
-                    state.rot_clockwise();
-                } else {
-                    state.rot_counterclockwise();
-                }
-            }
+        if arg == "--stack" {
+            stack = true;
+            continue;
         }
-        Command::Switch => {
-            let n = get!(state.stack.pop());
-            for _ in 0..n {
-                state.toggle_cc();
+
+        let (flag, value) = flag_value(&arg, &mut args)?;
+        match flag.as_str() {
+            "--codel-size" => {
+                let size = value.parse().map_err(|_| format!("Invalid codel size: {}", value))?;
+                read_opts.codel_size = Some(size);
             }
-        }
-        Command::Duplicate => {
-            let last = get!(state.stack.pop());
-            state.stack.push(last);
-            state.stack.push(last);
-        }
-        Command::Roll => {
-            let mut num_rolls = get!(state.stack.pop());
-            let mut depth = get!(state.stack.pop()) - 1;
-            let len = state.stack.len() as i32;
-            trace!("ROLL: {}, {}, {}", num_rolls, depth, len);
-            trace!("ROLL STACK: {:?}", state.stack);
-            if depth < 0 {
-                return;
+            "--input" => input = Some(value),
+            "--unknown-colors" => read_opts.unknown_colors = value.parse()?,
+            "--overflow" => overflow = value.parse()?,
+            "--delay" => {
+                let millis = value.parse().map_err(|_| format!("Invalid delay: {}", value))?;
+                delay = Some(Duration::from_millis(millis));
             }
-            // NOTE: This may not be right, but it's how I interpret the spec
-            if depth >= len {
-                return;
+            "-o" | "--output" => output = Some(value),
+            "--format" => format = value.parse()?,
+            "--emit" => emit = value.parse()?,
+            "--profile" => profile = Some(value),
+            "--coverage" => coverage = Some(value),
+            "--scale" => {
+                let size = value.parse()
+                    .ok()
+                    .filter(|&scale| scale > 0)
+                    .ok_or_else(|| format!("Invalid scale: {}", value))?;
+                scale = Some(size);
             }
-            if num_rolls < 0 {
-                num_rolls = -num_rolls;
-                depth = len - num_rolls;
+            "--max-steps" => {
+                let limit = value.parse().map_err(|_| format!("Invalid step limit: {}", value))?;
+                max_steps = Some(limit);
             }
-            for _ in 0..num_rolls {
-                let mut d = 0;
-                while d < depth {
-                    let offset: usize = (len - d - 1) as usize;
-                    state.stack.swap(offset, offset - 1);
-                    d += 1;
-                }
+            "--timeout" => {
+                let secs = value.parse::<f64>()
+                    .ok()
+                    .filter(|secs| *secs >= 0.0 && secs.is_finite())
+                    .ok_or_else(|| format!("Invalid timeout: {}", value))?;
+                timeout = Some(Duration::from_secs_f64(secs));
             }
-        }
-        Command::In(dtype) => {
-            let mut input = String::new();
-            get!(std::io::stdin().read_line(&mut input).ok());
-            let n: i32 = get!(input.parse().ok());
-            if dtype == DataType::Char {
-                get!(std::char::from_u32(n as u32));
+            "--max-stack" => {
+                let limit = value.parse().map_err(|_| format!("Invalid stack limit: {}", value))?;
+                max_stack = Some(limit);
             }
-            state.stack.push(n);
-        }
-        Command::Out(dtype) => {
-            let output = get!(state.stack.pop());
-            match dtype {
-                DataType::Number => write!(std::io::stdout(), "{}", output).ok(),
-                DataType::Char => {
-                    let outchar = get!(std::char::from_u32(output as u32));
-                    write!(std::io::stdout(), "{}", outchar).ok()
-                }
-            };
+            _ => return Err(format!("Unknown option: {}", flag)),
         }
     }
-    trace!("STACK: {:?}", state.stack);
+
+    // Options that only some modes use are rejected by the others rather than ignored
+    if visual && mode != Mode::Run {
+        return Err("--visual only applies to run".to_string());
+    }
+    if stack && mode != Mode::Record {
+        return Err("--stack only applies to record".to_string());
+    }
+    if delay.is_some() && !visual && mode != Mode::Record {
+        return Err("--delay only applies to record and run --visual".to_string());
+    }
+    // The terminal is taken over by visual mode, which only draws the program
+    if visual && (trace || profile.is_some() || coverage.is_some()) {
+        return Err("--visual cannot be combined with --trace, --profile or --coverage"
+            .to_string());
+    }
+    Ok(Options {
+        mode,
+        fname: fname.ok_or("Not enough arguments")?,
+        input,
+        read_opts,
+        overflow,
+        trace,
+        visual,
+        delay: delay.unwrap_or_else(|| Duration::from_millis(100)),
+        output,
+        format,
+        emit,
+        stack,
+        profile,
+        coverage,
+        scale,
+        max_steps,
+        timeout,
+        max_stack,
+    })
 }
 
-fn run_app() -> Result<(), String> {
-    try!(env_logger::init().map_err(|_| "Could not instantiate logger"));
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        return Err(String::from("Not enough arguments"));
+fn configure<R: BufRead, W: Write>(interp: &mut Interpreter<R, W>, opts: &Options) {
+    interp.set_overflow_policy(opts.overflow);
+    interp.set_max_steps(opts.max_steps);
+    interp.set_timeout(opts.timeout);
+    interp.set_max_stack(opts.max_stack);
+}
+
+/// Runs the program to completion, tracing, profiling and recording its coverage as requested.
+/// Profile and coverage are written even if the program fails.
+fn run<R: BufRead, W: Write>(interp: &mut Interpreter<R, W>,
+                             opts: &Options)
+                             -> Result<(), Box<dyn Error>> {
+    if !opts.trace && opts.profile.is_none() && opts.coverage.is_none() {
+        return Ok(interp.run()?);
     }
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let mut profile = opts.profile.as_ref().map(|_| Profile::new(interp.program()));
+    let mut coverage = opts.coverage.as_ref().map(|_| Coverage::new(interp.program()));
+    let res = loop {
+        let step = match interp.step() {
+            Ok(Some(step)) => step,
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(err),
+        };
+        if opts.trace {
+            trace::write_step(&mut stderr, interp.steps() - 1, &step, &interp.state().stack)?;
+        }
+        if let Some(ref mut profile) = profile {
+            profile.record(interp.program(), &step);
+        }
+        if let Some(ref mut coverage) = coverage {
+            coverage.record(interp.program(), &step);
+        }
+    };
 
-    let fname = &args[1];
-    let img = try!(reader::read(fname));
+    if let (Some(profile), Some(path)) = (profile, opts.profile.as_ref()) {
+        profile.heatmap(interp.program(), opts.scale.unwrap_or(1))
+            .save(path)
+            .map_err(|e| format!("Could not write {}: {}", path, e))?;
+        profile.write_report(interp.program(), &mut stderr)?;
+    }
+    if let (Some(coverage), Some(path)) = (coverage, opts.coverage.as_ref()) {
+        coverage.annotate(interp.program(), opts.scale.unwrap_or(1))
+            .save(path)
+            .map_err(|e| format!("Could not write {}: {}", path, e))?;
+        coverage.write_summary(interp.program(), &mut stderr)?;
+    }
+    Ok(res?)
+}
 
-    // interpret code
-    let mut state = State::new();
+/// Runs the program while drawing it in the terminal after every step. Program output is
+/// shown as part of the drawing rather than written to stdout directly.
+fn run_visual<R: BufRead>(interp: &mut Interpreter<R, Vec<u8>>,
+                          opts: &Options)
+                          -> Result<(), Box<dyn Error>> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b[2J")?;
     loop {
-        let mut seen_white = false;
-        let blk = ColorBlock::from_position_in_img(&img, &state.pos);
-        state.pos = blk.boundary_codel_position(&state.dp(), &state.cc());
+        let frame = visual::render_frame(interp, &String::from_utf8_lossy(interp.output()));
+        visual::redraw(&mut stdout, &frame)?;
+        if interp.step()?.is_none() {
+            return Ok(());
+        }
+        thread::sleep(opts.delay);
+    }
+}
 
-        if blk.color == (255, 255, 255) {
-            debug!("Sliding through white color block");
-            seen_white = true;
-            while !would_hit_restriction(&img, &state) &&
-                  util::get_px(&img, &state.peek_pos()) == (0, 0, 0) {
-                state.advance();
-            }
+/// Records the run as a GIF, up to the step limit or `DEFAULT_RECORDED_STEPS` steps. The
+/// recording is kept even if the program fails.
+fn record<R: BufRead, W: Write>(interp: &mut Interpreter<R, W>,
+                                opts: &Options)
+                                -> Result<(), Box<dyn Error>> {
+    let path = opts.output.as_ref().ok_or("record requires an output file: -o <file.gif>")?;
+    let file = File::create(path).map_err(|e| format!("Could not create {}: {}", path, e))?;
+    let mut recorder = Recorder::new(BufWriter::new(file),
+                                     interp.program(),
+                                     opts.scale.unwrap_or(8),
+                                     opts.stack,
+                                     opts.delay)?;
+    recorder.write_frame(interp.program(), interp.state())?;
+    let limit = opts.max_steps.unwrap_or(DEFAULT_RECORDED_STEPS);
+    while interp.steps() < limit && interp.step()?.is_some() {
+        recorder.write_frame(interp.program(), interp.state())?;
+    }
+    Ok(())
+}
+
+/// Calls `write` with `--output`, or stdout if that is not given.
+fn write_output<F>(opts: &Options, write: F) -> Result<(), Box<dyn Error>>
+    where F: FnOnce(&mut dyn Write) -> io::Result<()>
+{
+    match opts.output {
+        Some(ref path) => {
+            let file = File::create(path).map_err(|e| format!("Could not create {}: {}", path, e))?;
+            let mut out = BufWriter::new(file);
+            write(&mut out)?;
+            out.flush()?;
         }
+        None => write(&mut io::stdout().lock())?,
+    }
+    Ok(())
+}
 
-        // Boundary / end of program checks
-        let orig_dp = state.dp();
-        let orig_cc = state.cc();
-        let mut toggle_cc = true;
-        let mut first_restriction_check = true;
-        while would_hit_restriction(&img, &state) {
-            let is_end_of_program = !first_restriction_check && state.dp() == orig_dp &&
-                                    state.cc() == orig_cc;
-            if is_end_of_program {
-                info!("END OF PROGRAM!");
-                return Ok(());
-            }
+/// Writes the control flow graph to `--output`, or stdout if that is not given.
+fn write_cfg(program: &Program, opts: &Options) -> Result<(), Box<dyn Error>> {
+    let cfg = Cfg::new(program);
+    write_output(opts, |mut out| cfg.write(program, opts.format, &mut out))
+}
 
-            first_restriction_check = false;
-            if toggle_cc {
-                state.toggle_cc();
-                toggle_cc = false;
-            } else {
-                state.rot_clockwise();
-                toggle_cc = true;
-            }
+/// Compiles the program to `--output`, or stdout if that is not given. The compiled program
+/// handles overflow according to `--overflow`.
+fn compile(program: &Program, opts: &Options) -> Result<(), Box<dyn Error>> {
+    let emit_opts = emit::Options {
+        overflow: opts.overflow,
+    };
+    write_output(opts, |mut out| emit::write(program, opts.emit, &emit_opts, &mut out))
+}
 
-            state.pos = ColorBlock::from_position_in_img(&img, &state.pos)
-                .boundary_codel_position(&state.dp(), &state.cc());
-        }
+/// Assembles or compiles the source file into an image with codels of `--codel-size` pixels, 1
+/// by default.
+fn build(opts: &Options) -> Result<(), Box<dyn Error>> {
+    let path = opts.output.as_ref().ok_or("an output file is required: -o <file.png>")?;
+    let mut src = String::new();
+    File::open(&opts.fname)
+        .and_then(|mut file| file.read_to_string(&mut src))
+        .map_err(|e| format!("Could not read {}: {}", opts.fname, e))?;
+    let img = if opts.mode == Mode::Asm {
+        let instrs = asm::parse(&src).map_err(|e| format!("{}: {}", opts.fname, e))?;
+        asm::assemble(&instrs)?
+    } else {
+        let script = lang::parse(&src).map_err(|e| format!("{}: {}", opts.fname, e))?;
+        layout::render(&lang::compile(&script))
+    };
+    reader::upsample(&img, opts.read_opts.codel_size.unwrap_or(1))
+        .save(path)
+        .map_err(|e| format!("Could not write {}: {}", path, e))?;
+    Ok(())
+}
 
-        // Advance to next color block and exec color cmd
-        let last_pos = state.pos;
-        state.advance();
-        let nextcolor = util::get_px(&img, &state.pos);
-        seen_white = seen_white || nextcolor == (255, 255, 255);
+/// Reads debugger commands from stdin until the user quits. Program output goes to stdout as
+/// well, while program input is taken from `--input`.
+fn debug<R: BufRead, W: Write>(interp: Interpreter<R, W>) -> io::Result<()> {
+    let mut debugger = Debugger::new(interp);
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut line = String::new();
+    loop {
+        write!(stdout, "(piet) ")?;
+        stdout.flush()?;
+        line.clear();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return writeln!(stdout);
+        }
+        if !debugger.execute(&line, &mut stdout)? {
+            return Ok(());
+        }
+    }
+}
 
-        if !seen_white {
-            debug!("Seen white: not executing command");
-            exec_cmd(&blk.color,
-                     &nextcolor,
-                     last_pos,
-                     state.pos,
-                     &mut state,
-                     &blk);
+fn run_app() -> Result<(), Box<dyn Error>> {
+    env_logger::init().map_err(|_| "Could not instantiate logger")?;
+    let opts = parse_args()?;
+    if opts.mode == Mode::Asm || opts.mode == Mode::Build {
+        return build(&opts);
+    }
+    let img = reader::read(&opts.fname, &opts.read_opts)?;
+    let input: Box<dyn BufRead> = match opts.input {
+        Some(ref path) => {
+            let file = File::open(path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+            Box::new(BufReader::new(file))
         }
+        // The debugger's own commands are read from stdin
+        None if opts.mode == Mode::Debug => Box::new(io::empty()),
+        None => Box::new(io::stdin().lock()),
+    };
+    if opts.visual && opts.mode == Mode::Run {
+        let mut interp = Interpreter::with_io(img, input, Vec::new())?;
+        configure(&mut interp, &opts);
+        return run_visual(&mut interp, &opts);
+    }
+    let mut interp = Interpreter::with_io(img, input, io::stdout())?;
+    configure(&mut interp, &opts);
+    match opts.mode {
+        Mode::Run => run(&mut interp, &opts)?,
+        Mode::Debug => debug(interp)?,
+        Mode::Record => record(&mut interp, &opts)?,
+        Mode::Disasm => disasm::write_listing(&mut io::stdout(), interp.program())?,
+        Mode::Cfg => write_cfg(interp.program(), &opts)?,
+        Mode::Compile => compile(interp.program(), &opts)?,
+        Mode::Asm | Mode::Build => unreachable!(),
+    }
+    Ok(())
+}
+
+fn exit_code(err: &(dyn Error + 'static)) -> i32 {
+    match err.downcast_ref::<PietError>() {
+        Some(&PietError::StepLimitExceeded { .. }) => EXIT_STEP_LIMIT,
+        Some(&PietError::Timeout { .. }) => EXIT_TIMEOUT,
+        Some(&PietError::StackLimitExceeded { .. }) => EXIT_STACK_LIMIT,
+        _ => 1,
     }
 }
 
@@ -306,8 +462,8 @@ fn main() {
     ::std::process::exit(match run_app() {
         Ok(_) => 0,
         Err(err) => {
-            writeln!(io::stderr(), "error: {}", err).unwrap();
-            1
+            eprintln!("error: {}", err);
+            exit_code(err.as_ref())
         }
     });
 }
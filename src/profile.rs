@@ -0,0 +1,126 @@
+//! Counts how often each block is entered, each transition taken and each command executed, to
+//! find the hot spots of a program
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use image::RgbImage;
+
+use command::Command;
+use interpreter::Step;
+use program::{BlockId, Program};
+use util;
+
+/// How many of the most frequent transitions `Profile::write_report` lists.
+const REPORTED_TRANSITIONS: usize = 10;
+
+/// Maps `heat` in [0, 1] onto a color going from blue through yellow to red.
+fn heat_color(heat: f64) -> (u8, u8, u8) {
+    let lerp = |from: f64, to: f64, t: f64| (from + (to - from) * t).round() as u8;
+    if heat < 0.5 {
+        let t = heat * 2.0;
+        (lerp(0.0, 255.0, t), lerp(0.0, 255.0, t), lerp(255.0, 0.0, t))
+    } else {
+        let t = (heat - 0.5) * 2.0;
+        (255, lerp(255.0, 0.0, t), 0)
+    }
+}
+
+#[derive(Debug)]
+pub struct Profile {
+    /// How often each block was entered, indexed by `BlockId`
+    entries: Vec<u64>,
+    transitions: HashMap<(BlockId, BlockId), u64>,
+    commands: HashMap<Command, u64>,
+}
+
+impl Profile {
+    /// Creates an empty profile of `program`, which has only entered its first block.
+    pub fn new(program: &Program) -> Profile {
+        let mut entries = vec![0; program.blocks().len()];
+        if let Some((id, _, _)) = program.start() {
+            entries[id] = 1;
+        }
+        Profile {
+            entries,
+            transitions: HashMap::new(),
+            commands: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, program: &Program, step: &Step) {
+        let (from, to) = (program.block_id(step.from_pos), program.block_id(step.to_pos));
+        // Only a program starting in white takes a step out of a white block, which leads into
+        // the block `new` already counted
+        if program.block(from).color.is_white() {
+            return;
+        }
+        self.entries[to] += 1;
+        *self.transitions.entry((from, to)).or_insert(0) += 1;
+        // Slides through white execute no command
+        if step.cmd != Command::Nop {
+            *self.commands.entry(step.cmd).or_insert(0) += 1;
+        }
+    }
+
+    /// How often the block `id` was entered.
+    pub fn entries(&self, id: BlockId) -> u64 {
+        self.entries[id]
+    }
+
+    /// How often the program moved from block `from` directly into block `to`.
+    pub fn transitions(&self, from: BlockId, to: BlockId) -> u64 {
+        self.transitions.get(&(from, to)).cloned().unwrap_or(0)
+    }
+
+    /// Every command that was executed along with how often, most frequent first.
+    pub fn command_counts(&self) -> Vec<(Command, u64)> {
+        let mut counts: Vec<_> = self.commands.iter().map(|(&cmd, &n)| (cmd, n)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_string().cmp(&b.0.to_string())));
+        counts
+    }
+
+    /// Draws the program with every codel scaled up to `scale`x`scale` pixels. Blocks that were
+    /// never entered are dimmed, while the others are tinted from blue to red by how often they
+    /// were entered, on a logarithmic scale.
+    pub fn heatmap(&self, program: &Program, scale: u32) -> RgbImage {
+        let max = self.entries.iter().cloned().max().unwrap_or(0);
+        program.render(scale, |id, blk| {
+            match self.entries[id] {
+                0 => util::mix(blk.color.rgb_color, (128, 128, 128), 0.3),
+                n => {
+                    let heat = ((n + 1) as f64).ln() / ((max + 1) as f64).ln();
+                    util::mix(blk.color.rgb_color, heat_color(heat), 0.35)
+                }
+            }
+        })
+    }
+
+    /// Writes the command histogram and the most frequent transitions as text.
+    pub fn write_report<W: Write>(&self, program: &Program, out: &mut W) -> io::Result<()> {
+        let total: u64 = self.commands.values().sum();
+        writeln!(out, "Commands executed: {}", total)?;
+        for (cmd, n) in self.command_counts() {
+            writeln!(out,
+                     "{:>12} {:>12} {:>5.1}%",
+                     cmd.to_string(),
+                     n,
+                     100.0 * n as f64 / total as f64)?;
+        }
+
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        writeln!(out, "Most frequent transitions:")?;
+        for (&(from, to), n) in transitions.into_iter().take(REPORTED_TRANSITIONS) {
+            let (from, to) = (program.block(from), program.block(to));
+            writeln!(out,
+                     "{:>12} {} {} -> {} {}",
+                     n,
+                     from.color.mnemonic,
+                     from.origin,
+                     to.color.mnemonic,
+                     to.origin)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+//! Splits a program into color blocks and works out every transition between them ahead of
+//! time, so that the interpreter can move from block to block in constant time.
+
+use std::collections::{HashSet, VecDeque};
+
+use image::{Rgb, RgbImage};
+
+use color::Color;
+use color_block::ColorBlock;
+use command::Command;
+use error::PietError;
+use state::{Direction, Position};
+
+/// Identifies a block of a `Program`. Blocks are numbered in the row-major order of their first
+/// codel.
+pub type BlockId = usize;
+
+/// A state the interpreter can be in between steps: the block it is in, with the DP and CC.
+pub type Node = (BlockId, Direction, Direction);
+
+/// Every DP/CC combination, in the order used to index a block's tables.
+pub static DIRECTIONS: [(Direction, Direction); 8] = [(Direction::Right, Direction::Left),
+                                                      (Direction::Right, Direction::Right),
+                                                      (Direction::Down, Direction::Left),
+                                                      (Direction::Down, Direction::Right),
+                                                      (Direction::Left, Direction::Left),
+                                                      (Direction::Left, Direction::Right),
+                                                      (Direction::Up, Direction::Left),
+                                                      (Direction::Up, Direction::Right)];
+
+fn direction_index(dp: Direction, cc: Direction) -> usize {
+    dp as usize * 2 + if cc == Direction::Left { 0 } else { 1 }
+}
+
+fn toggle(cc: Direction) -> Direction {
+    cc + Direction::Down
+}
+
+/// How a block is left, and which colored codel is entered next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exit {
+    /// The codel the block is left through
+    pub from: Position,
+    /// The DP and CC the block is left with, once any restricted exits have been tried
+    pub dp: Direction,
+    pub cc: Direction,
+    pub to: Position,
+    /// The DP and CC on entering `to`. These only differ from `dp` and `cc` when sliding
+    /// through white.
+    pub dp_after: Direction,
+    pub cc_after: Direction,
+    /// Whether the move slid through white, in which case no command is executed
+    pub through_white: bool,
+    /// The command executed by the move, `Command::Nop` when sliding through white
+    pub cmd: Command,
+}
+
+#[derive(Debug)]
+pub struct Block {
+    pub color: Color,
+    /// The number of codels in the block
+    pub size: usize,
+    /// The first codel of the block in row-major order
+    pub origin: Position,
+    boundary: [Position; 8],
+    exits: [Option<Exit>; 8],
+}
+
+impl Block {
+    /// The codel the block would be left through with the given DP and CC, were that exit not
+    /// restricted.
+    pub fn boundary_codel(&self, dp: Direction, cc: Direction) -> Position {
+        self.boundary[direction_index(dp, cc)]
+    }
+
+    /// How the block is left when the interpreter is in it with the given DP and CC. `None`
+    /// means every exit is restricted and the program terminates. Always `None` for black and
+    /// white blocks, see `Program::exit_from`.
+    pub fn exit(&self, dp: Direction, cc: Direction) -> Option<&Exit> {
+        self.exits[direction_index(dp, cc)].as_ref()
+    }
+}
+
+#[derive(Debug)]
+pub struct Program {
+    img: RgbImage,
+    /// The id of the block each codel belongs to, in row-major order
+    labels: Vec<u32>,
+    blocks: Vec<Block>,
+}
+
+impl Program {
+    /// Labels every codel of `img`, whose pixels must be one codel each, with its block.
+    /// Fails if any codel is not one of the piet colors.
+    pub fn new(img: RgbImage) -> Result<Program, PietError> {
+        let (width, height) = img.dimensions();
+        let mut visited = vec![false; width as usize * height as usize];
+        let mut labels = vec![0; visited.len()];
+        let mut blocks = Vec::new();
+        for top in 0..height {
+            for left in 0..width {
+                let origin = Position { left, top };
+                if visited[top as usize * width as usize + left as usize] {
+                    continue;
+                }
+                let blk = ColorBlock::fill(&img, &origin, &mut visited)?;
+                for codel in blk.codels() {
+                    labels[codel.top as usize * width as usize + codel.left as usize] =
+                        blocks.len() as u32;
+                }
+                let mut boundary = [origin; 8];
+                for (i, &(dp, cc)) in DIRECTIONS.iter().enumerate() {
+                    boundary[i] = blk.boundary_codel_position(&dp, &cc);
+                }
+                blocks.push(Block {
+                    color: Color::from_px(&blk.color)?,
+                    size: blk.codels().len(),
+                    origin,
+                    boundary,
+                    exits: [None; 8],
+                });
+            }
+        }
+
+        let mut program = Program {
+            img,
+            labels,
+            blocks,
+        };
+        for id in 0..program.blocks.len() {
+            let color = program.blocks[id].color;
+            if color.is_white() || color.is_black() {
+                continue;
+            }
+            let mut exits = [None; 8];
+            for (i, &(dp, cc)) in DIRECTIONS.iter().enumerate() {
+                exits[i] = program.find_exit(id, dp, cc);
+            }
+            program.blocks[id].exits = exits;
+        }
+        debug!("Found {} color blocks", program.blocks.len());
+        Ok(program)
+    }
+
+    pub fn image(&self) -> &RgbImage {
+        &self.img
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id]
+    }
+
+    pub fn block_id(&self, pos: Position) -> BlockId {
+        self.labels[pos.top as usize * self.img.width() as usize + pos.left as usize] as BlockId
+    }
+
+    pub fn block_at(&self, pos: Position) -> &Block {
+        self.block(self.block_id(pos))
+    }
+
+    /// Draws the program with every codel scaled up to `scale`x`scale` pixels, in the color
+    /// `draw` picks for the block it belongs to.
+    pub fn render<F>(&self, scale: u32, draw: F) -> RgbImage
+        where F: Fn(BlockId, &Block) -> Rgb<u8>
+    {
+        RgbImage::from_fn(self.img.width() * scale, self.img.height() * scale, |x, y| {
+            let id = self.block_id(Position {
+                left: x / scale,
+                top: y / scale,
+            });
+            draw(id, &self.blocks[id])
+        })
+    }
+
+    /// How the interpreter moves on from the codel at `pos` with the given DP and CC. Unlike
+    /// `Block::exit`, this also handles `pos` being white, which can only happen at the very
+    /// start of a program.
+    pub fn exit_from(&self, pos: Position, dp: Direction, cc: Direction) -> Option<Exit> {
+        let blk = self.block_at(pos);
+        if !blk.color.is_white() {
+            return blk.exit(dp, cc).cloned();
+        }
+        self.slide(pos, dp, cc).map(|(to, dp_after, cc_after)| {
+            Exit {
+                from: pos,
+                dp,
+                cc,
+                to,
+                dp_after,
+                cc_after,
+                through_white: true,
+                cmd: Command::Nop,
+            }
+        })
+    }
+
+    /// The node the program starts in: the top left block with the DP pointing right and the
+    /// CC left, or if that is white, the block the program first slides into. `None` if the
+    /// program never leaves the white block it starts in.
+    pub fn start(&self) -> Option<Node> {
+        let origin = Position { left: 0, top: 0 };
+        let (dp, cc) = (Direction::Right, Direction::Left);
+        if !self.block_at(origin).color.is_white() {
+            return Some((self.block_id(origin), dp, cc));
+        }
+        self.exit_from(origin, dp, cc)
+            .map(|exit| (self.block_id(exit.to), exit.dp_after, exit.cc_after))
+    }
+
+    /// The nodes a step out of `node` may lead to. Which way `Pointer` and `Switch` turn
+    /// depends on the stack, so every possibility is included for those.
+    pub fn successors(&self, node: Node) -> Vec<Node> {
+        let (id, dp, cc) = node;
+        let exit = match self.blocks[id].exit(dp, cc) {
+            Some(exit) => exit,
+            None => return Vec::new(),
+        };
+        let to = self.block_id(exit.to);
+        match exit.cmd {
+            Command::Pointer => {
+                let mut dp = exit.dp_after;
+                (0..4)
+                    .map(|_| {
+                        let node = (to, dp, exit.cc_after);
+                        dp += Direction::Right;
+                        node
+                    })
+                    .collect()
+            }
+            Command::Switch => {
+                vec![(to, exit.dp_after, exit.cc_after), (to, exit.dp_after, toggle(exit.cc_after))]
+            }
+            _ => vec![(to, exit.dp_after, exit.cc_after)],
+        }
+    }
+
+    /// Every node reachable from `start`, in breadth-first order.
+    pub fn reachable(&self) -> Vec<Node> {
+        let mut seen = HashSet::new();
+        let mut order = Vec::new();
+        let mut queue: VecDeque<Node> = self.start().into_iter().collect();
+        while let Some(node) = queue.pop_front() {
+            if !seen.insert(node) {
+                continue;
+            }
+            order.push(node);
+            queue.extend(self.successors(node));
+        }
+        order
+    }
+
+    /// The codel next to `pos` in direction `dir`, or `None` if that is past the edge of the
+    /// program or black.
+    pub fn neighbor(&self, pos: Position, dir: Direction) -> Option<Position> {
+        let (width, height) = self.img.dimensions();
+        let next = match dir {
+            Direction::Right if pos.left + 1 < width => Position { left: pos.left + 1, ..pos },
+            Direction::Down if pos.top + 1 < height => Position { top: pos.top + 1, ..pos },
+            Direction::Left if pos.left > 0 => Position { left: pos.left - 1, ..pos },
+            Direction::Up if pos.top > 0 => Position { top: pos.top - 1, ..pos },
+            _ => return None,
+        };
+        if self.block_at(next).color.is_black() {
+            None
+        } else {
+            Some(next)
+        }
+    }
+
+    /// Tries the exits of a block as the spec describes, toggling the CC and rotating the DP
+    /// until a move out of the block is possible.
+    fn find_exit(&self, id: BlockId, mut dp: Direction, mut cc: Direction) -> Option<Exit> {
+        let blk = &self.blocks[id];
+        for attempt in 0..8 {
+            if attempt > 0 {
+                if attempt % 2 == 1 {
+                    cc = toggle(cc);
+                } else {
+                    dp += Direction::Right;
+                }
+            }
+            let from = blk.boundary_codel(dp, cc);
+            let to = match self.neighbor(from, dp) {
+                Some(to) => to,
+                None => continue,
+            };
+            let to_color = self.block_at(to).color;
+            if !to_color.is_white() {
+                return Some(Exit {
+                    from,
+                    dp,
+                    cc,
+                    to,
+                    dp_after: dp,
+                    cc_after: cc,
+                    through_white: false,
+                    cmd: Command::from_colors(&blk.color, &to_color),
+                });
+            }
+            return self.slide(to, dp, cc).map(|(to, dp_after, cc_after)| {
+                Exit {
+                    from,
+                    dp,
+                    cc,
+                    to,
+                    dp_after,
+                    cc_after,
+                    through_white: true,
+                    cmd: Command::Nop,
+                }
+            });
+        }
+        None
+    }
+
+    /// Slides from the white codel at `pos` in the direction of the DP until a colored codel is
+    /// entered, returning it along with the DP and CC at that point. Each restriction hit on
+    /// the way toggles the CC and rotates the DP clockwise. Returns `None` if the slide retraces
+    /// its route, which terminates the program.
+    fn slide(&self,
+             mut pos: Position,
+             mut dp: Direction,
+             mut cc: Direction)
+             -> Option<(Position, Direction, Direction)> {
+        // Any loop must pass through a restriction, so only those need to be remembered
+        let mut restrictions = HashSet::new();
+        loop {
+            match self.neighbor(pos, dp) {
+                Some(next) => {
+                    pos = next;
+                    if !self.block_at(pos).color.is_white() {
+                        return Some((pos, dp, cc));
+                    }
+                }
+                None => {
+                    if !restrictions.insert((pos, dp, cc)) {
+                        return None;
+                    }
+                    cc = toggle(cc);
+                    dp += Direction::Right;
+                }
+            }
+        }
+    }
+}
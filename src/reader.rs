@@ -1,16 +1,172 @@
-//! Reads an image
-//! Currently only supports 1px codels
+//! Reads an image and scales it down into a grid of codels, where every pixel of the returned
+//! image corresponds to exactly one codel of the program.
 
-use std::error::Error;
+use std::collections::HashMap;
 use std::path::Path;
+use std::str::FromStr;
 
-use image::{self, RgbImage};
+use image::{self, Rgb, RgbImage};
 
-pub fn read<P>(path: P) -> Result<RgbImage, String>
+use color::{self, Color};
+use error::PietError;
+use state::Position;
+use util;
+
+/// How to treat pixels that are not one of the 20 piet colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownColors {
+    White,
+    Black,
+    /// Use the piet color that looks most similar
+    Nearest,
+    /// Fail to load the image
+    #[default]
+    Error,
+}
+
+impl FromStr for UnknownColors {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<UnknownColors, String> {
+        match s {
+            "white" => Ok(UnknownColors::White),
+            "black" => Ok(UnknownColors::Black),
+            "nearest" => Ok(UnknownColors::Nearest),
+            "error" => Ok(UnknownColors::Error),
+            _ => Err(format!("Unknown color handling mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// The size of a codel in pixels, detected from the image when `None`
+    pub codel_size: Option<u32>,
+    pub unknown_colors: UnknownColors,
+}
+
+pub fn read<P>(path: P, opts: &Options) -> Result<RgbImage, PietError>
     where P: AsRef<Path>
 {
-    match image::open(path) {
-        Ok(img) => Ok(img.to_rgb()),
-        Err(e) => Err(format!("Could not open image: {}", e.description())),
+    let mut img = image::open(path)?.to_rgb();
+    // Colors are replaced before the codel size is detected, so that noise such as JPEG
+    // artifacts does not break codels up
+    if opts.unknown_colors != UnknownColors::Error {
+        replace_unknown_colors(&mut img, opts.unknown_colors)?;
+    }
+    let codel_size = opts.codel_size.unwrap_or_else(|| detect_codel_size(&img));
+    // Invalid colors are reported before the codel size is checked, which a stray pixel would
+    // otherwise be blamed on. They are located by codel like every other error.
+    if opts.unknown_colors == UnknownColors::Error {
+        let size = codel_size.max(1);
+        replace_unknown_colors(&mut img, UnknownColors::Error).map_err(|e| match e {
+            PietError::InvalidColor { rgb, pos: Some(pos) } => {
+                PietError::InvalidColor {
+                    rgb,
+                    pos: Some(Position {
+                        left: pos.left / size,
+                        top: pos.top / size,
+                    }),
+                }
+            }
+            e => e,
+        })?;
+    }
+    // Codels of noisy images are rarely a single color, so in nearest mode each is read from
+    // its top-left pixel
+    if codel_size == 0 || img.width() % codel_size != 0 || img.height() % codel_size != 0 ||
+       (opts.unknown_colors != UnknownColors::Nearest && !has_uniform_codels(&img, codel_size)) {
+        return Err(PietError::InvalidCodelSize {
+            size: codel_size,
+            width: img.width(),
+            height: img.height(),
+        });
+    }
+    debug!("Using codel size {}", codel_size);
+    Ok(downsample(&img, codel_size))
+}
+
+/// Replaces every pixel that is not a piet color according to `mode`. In
+/// `UnknownColors::Error` mode, the error gives the position of the offending pixel of `img`.
+pub fn replace_unknown_colors(img: &mut RgbImage, mode: UnknownColors) -> Result<(), PietError> {
+    let mut replacements = HashMap::new();
+    for (x, y, px) in img.enumerate_pixels_mut() {
+        let rgb = (px[0], px[1], px[2]);
+        if Color::from_px(&rgb).is_ok() {
+            continue;
+        }
+        let (r, g, b) = match mode {
+            UnknownColors::White => (255, 255, 255),
+            UnknownColors::Black => (0, 0, 0),
+            UnknownColors::Nearest => {
+                *replacements.entry(rgb).or_insert_with(|| color::nearest_piet_color(&rgb))
+            }
+            UnknownColors::Error => {
+                return Err(PietError::InvalidColor {
+                    rgb,
+                    pos: Some(Position { left: x, top: y }),
+                })
+            }
+        };
+        *px = Rgb([r, g, b]);
     }
+    Ok(())
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Detects the codel size of an image by taking the GCD of the lengths of every run of
+/// same-colored pixels across all rows and columns.
+pub fn detect_codel_size(img: &RgbImage) -> u32 {
+    let (width, height) = img.dimensions();
+    let mut size = gcd(width, height);
+    let mut run_gcd = |line: &mut dyn Iterator<Item = (u8, u8, u8)>| {
+        let mut prev = None;
+        let mut run = 0;
+        for px in line {
+            if prev.is_some() && prev != Some(px) {
+                size = gcd(size, run);
+                run = 0;
+            }
+            prev = Some(px);
+            run += 1;
+        }
+        size = gcd(size, run);
+    };
+
+    for top in 0..height {
+        run_gcd(&mut (0..width).map(|left| util::get_px(img, &Position { left, top })));
+    }
+    for left in 0..width {
+        run_gcd(&mut (0..height).map(|top| util::get_px(img, &Position { left, top })));
+    }
+    size.max(1)
+}
+
+/// Whether every `codel_size`x`codel_size` square of `img` is a single color.
+fn has_uniform_codels(img: &RgbImage, codel_size: u32) -> bool {
+    img.enumerate_pixels()
+        .all(|(x, y, px)| px == img.get_pixel(x - x % codel_size, y - y % codel_size))
+}
+
+/// Scales an image down so that each `codel_size`x`codel_size` square becomes a single pixel,
+/// the color of its top-left pixel.
+pub fn downsample(img: &RgbImage, codel_size: u32) -> RgbImage {
+    if codel_size == 1 {
+        return img.clone();
+    }
+    let (width, height) = (img.width() / codel_size, img.height() / codel_size);
+    RgbImage::from_fn(width,
+                      height,
+                      |x, y| *img.get_pixel(x * codel_size, y * codel_size))
+}
+
+/// The inverse of `downsample`, scaling an image up so that each pixel becomes a
+/// `codel_size`x`codel_size` square.
+pub fn upsample(img: &RgbImage, codel_size: u32) -> RgbImage {
+    RgbImage::from_fn(img.width() * codel_size,
+                      img.height() * codel_size,
+                      |x, y| *img.get_pixel(x / codel_size, y / codel_size))
 }
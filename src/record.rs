@@ -0,0 +1,237 @@
+//! Records a run as an animated GIF with one frame per step, for explaining what a program does
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use gif::{self, SetParameter};
+use image::{Rgb, RgbImage};
+
+use color::PIET_COLORS;
+use program::Program;
+use state::{Direction, Position, State};
+
+const OUTLINE: (u8, u8, u8) = (255, 128, 0);
+const ARROW: (u8, u8, u8) = (64, 64, 64);
+const SIDEBAR: (u8, u8, u8) = (32, 32, 32);
+const TEXT: (u8, u8, u8) = (224, 224, 224);
+
+/// Glyphs are 3x5 pixels, drawn `FONT_SCALE` times larger with a pixel of spacing.
+const FONT_SCALE: u32 = 2;
+const GLYPH_WIDTH: u32 = 4 * FONT_SCALE;
+const LINE_HEIGHT: u32 = 6 * FONT_SCALE;
+const SIDEBAR_MARGIN: u32 = 4;
+const SIDEBAR_CHARS: usize = 12;
+const SIDEBAR_WIDTH: u32 = SIDEBAR_CHARS as u32 * GLYPH_WIDTH + 2 * SIDEBAR_MARGIN;
+/// Frames with a sidebar are made at least tall enough to list this many values.
+const SIDEBAR_MIN_ROWS: u32 = 8;
+
+/// Every color a frame may contain, which makes up the GIF's global palette.
+fn palette() -> Vec<(u8, u8, u8)> {
+    let mut colors = PIET_COLORS.to_vec();
+    colors.extend_from_slice(&[OUTLINE, ARROW, SIDEBAR, TEXT]);
+    colors
+}
+
+/// Rows of a 3x5 glyph, most significant bit leftmost. Only what is needed to show numbers.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        _ => [0; 5],
+    }
+}
+
+fn put(img: &mut RgbImage, x: u32, y: u32, (r, g, b): (u8, u8, u8)) {
+    if x < img.width() && y < img.height() {
+        img.put_pixel(x, y, Rgb([r, g, b]));
+    }
+}
+
+fn draw_text(img: &mut RgbImage, left: u32, top: u32, text: &str) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph_left = left + i as u32 * GLYPH_WIDTH;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..FONT_SCALE {
+                    for dx in 0..FONT_SCALE {
+                        put(img,
+                            glyph_left + col * FONT_SCALE + dx,
+                            top + row as u32 * FONT_SCALE + dy,
+                            TEXT);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether the pixel at `(x, y)` of a `scale`x`scale` codel lies within a triangle pointing
+/// in direction `dir`.
+fn in_arrow(x: u32, y: u32, scale: u32, dir: Direction) -> bool {
+    let last = scale - 1;
+    let (along, across) = match dir {
+        Direction::Right => (x, y),
+        Direction::Left => (last - x, y),
+        Direction::Down => (y, x),
+        Direction::Up => (last - y, x),
+    };
+    let (along, across, size) = (f64::from(along) + 0.5, f64::from(across) + 0.5, f64::from(scale));
+    along >= size * 0.25 && along <= size * 0.75 &&
+    (across - size / 2.0).abs() <= (size * 0.75 - along) * 0.6
+}
+
+/// The size of the frames drawn for `program`.
+pub fn frame_size(program: &Program, scale: u32, show_stack: bool) -> (u32, u32) {
+    let (width, height) = program.image().dimensions();
+    if show_stack {
+        (width * scale + SIDEBAR_WIDTH,
+         (height * scale).max(SIDEBAR_MIN_ROWS * LINE_HEIGHT + 2 * SIDEBAR_MARGIN))
+    } else {
+        (width * scale, height * scale)
+    }
+}
+
+/// Draws the program with every codel scaled up to `scale`x`scale` pixels, the current block
+/// outlined and an arrow in the current codel pointing along the DP. With `show_stack`, the
+/// stack is listed top first in a sidebar to the right.
+pub fn render_frame(program: &Program, state: &State, scale: u32, show_stack: bool) -> RgbImage {
+    let (width, height) = frame_size(program, scale, show_stack);
+    let (r, g, b) = SIDEBAR;
+    let mut img = RgbImage::from_pixel(width, height, Rgb([r, g, b]));
+    let codels = program.render(scale, |_, blk| {
+        let (r, g, b) = blk.color.rgb_color;
+        Rgb([r, g, b])
+    });
+    for (x, y, px) in codels.enumerate_pixels() {
+        img.put_pixel(x, y, *px);
+    }
+
+    let current = program.block_id(state.pos);
+    let thickness = (scale / 6).max(1);
+    let (codels_wide, codels_high) = program.image().dimensions();
+    for top in 0..codels_high {
+        for left in 0..codels_wide {
+            let pos = Position { left, top };
+            if program.block_id(pos) != current {
+                continue;
+            }
+            let outside = |dx: i64, dy: i64| {
+                let (x, y) = (i64::from(left) + dx, i64::from(top) + dy);
+                x < 0 || y < 0 || x >= i64::from(codels_wide) || y >= i64::from(codels_high) ||
+                program.block_id(Position {
+                    left: x as u32,
+                    top: y as u32,
+                }) != current
+            };
+            let edges = (outside(-1, 0), outside(1, 0), outside(0, -1), outside(0, 1));
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    if edges.0 && dx < thickness || edges.1 && dx >= scale - thickness ||
+                       edges.2 && dy < thickness ||
+                       edges.3 && dy >= scale - thickness {
+                        put(&mut img, left * scale + dx, top * scale + dy, OUTLINE);
+                    }
+                }
+            }
+        }
+    }
+
+    if scale >= 5 {
+        for dy in 0..scale {
+            for dx in 0..scale {
+                if in_arrow(dx, dy, scale, state.dp()) {
+                    put(&mut img, state.pos.left * scale + dx, state.pos.top * scale + dy, ARROW);
+                }
+            }
+        }
+    }
+
+    if show_stack {
+        let left = codels_wide * scale + SIDEBAR_MARGIN;
+        let rows = (height.saturating_sub(2 * SIDEBAR_MARGIN) / LINE_HEIGHT) as usize;
+        for (row, value) in state.stack.iter().rev().take(rows).enumerate() {
+            let mut text = value.to_string();
+            if row + 1 == rows && state.stack.len() > rows {
+                text = "...".to_string();
+            } else if text.len() > SIDEBAR_CHARS {
+                text.truncate(SIDEBAR_CHARS - 2);
+                text.push_str("..");
+            }
+            draw_text(&mut img, left, SIDEBAR_MARGIN + row as u32 * LINE_HEIGHT, &text);
+        }
+    }
+    img
+}
+
+/// Writes frames of a program's run to a looping GIF.
+pub struct Recorder<W: Write> {
+    encoder: gif::Encoder<W>,
+    palette: Vec<(u8, u8, u8)>,
+    scale: u32,
+    show_stack: bool,
+    /// How long each frame is shown, in hundredths of a second
+    delay: u16,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Starts a GIF for `program`, showing each frame for `delay`. Fails if frames would be
+    /// too large for a GIF.
+    pub fn new(out: W,
+               program: &Program,
+               scale: u32,
+               show_stack: bool,
+               delay: Duration)
+               -> io::Result<Recorder<W>> {
+        let (width, height) = frame_size(program, scale, show_stack);
+        if width > u32::from(u16::MAX) || height > u32::from(u16::MAX) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("Frames of {}x{} are too large for a GIF",
+                                              width,
+                                              height)));
+        }
+        let palette = palette();
+        let flat: Vec<u8> = palette.iter().flat_map(|&(r, g, b)| vec![r, g, b]).collect();
+        let mut encoder = gif::Encoder::new(out, width as u16, height as u16, &flat)?;
+        encoder.set(gif::Repeat::Infinite)?;
+        Ok(Recorder {
+            encoder,
+            palette,
+            scale,
+            show_stack,
+            delay: (delay.as_millis() / 10).min(u128::from(u16::MAX)) as u16,
+        })
+    }
+
+    /// Adds a frame showing the program in `state`.
+    pub fn write_frame(&mut self, program: &Program, state: &State) -> io::Result<()> {
+        let img = render_frame(program, state, self.scale, self.show_stack);
+        let palette = &self.palette;
+        let buffer = img.pixels()
+            .map(|px| {
+                palette.iter().position(|&c| c == (px[0], px[1], px[2])).unwrap_or(0) as u8
+            })
+            .collect();
+        let frame = gif::Frame {
+            width: img.width() as u16,
+            height: img.height() as u16,
+            delay: self.delay,
+            buffer: Cow::Owned(buffer),
+            ..gif::Frame::default()
+        };
+        self.encoder.write_frame(&frame)
+    }
+}
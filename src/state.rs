@@ -3,6 +3,8 @@
 use std::fmt;
 use std::ops::{Add, AddAssign};
 
+use value::Value;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Position {
     pub left: u32,
@@ -15,7 +17,7 @@ impl fmt::Display for Position {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Direction {
     Right,
     Down,
@@ -66,15 +68,41 @@ impl AddAssign for Direction {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct State {
-    pub stack: Vec<i32>,
+    pub stack: Vec<Value>,
     pub pos: Position,
     pub choosing_codel: bool,
     dp: Direction,
     cc: Direction,
 }
 
+/// How many values at the top of the stack are shown when displaying a `State`.
+const DISPLAYED_STACK_DEPTH: usize = 16;
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "position {}, DP {:?}, CC {:?}, stack [", self.pos, self.dp, self.cc)?;
+        let shown = self.stack.len().min(DISPLAYED_STACK_DEPTH);
+        if shown < self.stack.len() {
+            write!(f, "... {} more, ", self.stack.len() - shown)?;
+        }
+        for (i, value) in self.stack[self.stack.len() - shown..].iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
+}
+
 impl State {
     pub fn new() -> State {
         State {
@@ -94,6 +122,18 @@ impl State {
         self.cc
     }
 
+    pub fn set_dp(&mut self, dp: Direction) {
+        self.dp = dp;
+    }
+
+    /// Sets the CC, which is relative to the DP. Panics unless `cc` is `Left` or `Right`.
+    pub fn set_cc(&mut self, cc: Direction) {
+        assert!(cc == Direction::Left || cc == Direction::Right,
+                "The CC must be left or right, not {:?}",
+                cc);
+        self.cc = cc;
+    }
+
     pub fn codel_direction(&self) -> Direction {
         self.cc + self.dp
     }
@@ -0,0 +1,50 @@
+//! Writes execution traces in the shape of npiet's `-t` output, so that runs can be compared
+//! against other interpreters
+
+use std::io::{self, Write};
+
+use command::Command;
+use interpreter::Step;
+use state::{Direction, Position};
+use value::Value;
+
+/// The single letter npiet uses for a direction.
+pub fn direction_char(dir: Direction) -> char {
+    match dir {
+        Direction::Right => 'r',
+        Direction::Down => 'd',
+        Direction::Left => 'l',
+        Direction::Up => 'u',
+    }
+}
+
+fn codel(pos: Position, dp: Direction, cc: Direction) -> String {
+    format!("{},{}/{},{}",
+            pos.left,
+            pos.top,
+            direction_char(dp),
+            direction_char(cc))
+}
+
+/// Writes the block of lines npiet prints for `step`, the `n`th step of the program counting
+/// from 0: the transition, the command executed and the stack after it. The stack is listed
+/// top first as npiet does.
+pub fn write_step<W: Write>(out: &mut W, n: u64, step: &Step, stack: &[Value]) -> io::Result<()> {
+    writeln!(out,
+             "trace: step {}  ({} {} -> {} {}):",
+             n,
+             codel(step.from_pos, step.dp_before, step.cc_before),
+             step.from_color.abbreviation(),
+             codel(step.to_pos, step.dp_after, step.cc_after),
+             step.to_color.abbreviation())?;
+    match step.cmd {
+        Command::Nop => writeln!(out, "trace: white cell(s) crossed - continuing with no command")?,
+        Command::Push => writeln!(out, "action: push, value {}", step.pushed[0])?,
+        cmd => writeln!(out, "action: {}", cmd)?,
+    }
+    write!(out, "trace: stack ({} values):", stack.len())?;
+    for value in stack.iter().rev() {
+        write!(out, " {}", value)?;
+    }
+    writeln!(out)
+}
@@ -1,4 +1,4 @@
-use image;
+use image::{self, Rgb};
 
 use state::Position;
 
@@ -6,3 +6,9 @@ pub fn get_px(img: &image::RgbImage, pos: &Position) -> (u8, u8, u8) {
     let pxi = img.get_pixel(pos.left, pos.top);
     (pxi[0], pxi[1], pxi[2])
 }
+
+/// Blends two colors, giving `a` a weight of `weight_a` between 0 and 1.
+pub fn mix(a: (u8, u8, u8), b: (u8, u8, u8), weight_a: f64) -> Rgb<u8> {
+    let mix = |a: u8, b: u8| (f64::from(a) * weight_a + f64::from(b) * (1.0 - weight_a)) as u8;
+    Rgb([mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2)])
+}
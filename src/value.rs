@@ -0,0 +1,205 @@
+//! The type of values held on the piet stack.
+//!
+//! Values are `i64`s by default. Building with the `bigint` feature makes them arbitrary
+//! precision integers instead, so that arithmetic can never overflow.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+#[cfg(feature = "bigint")]
+use num_integer::Integer;
+#[cfg(feature = "bigint")]
+use num_traits::{ToPrimitive, Zero};
+
+/// Arithmetic needed by the interpreter from a stack value. Every operation returns `None`
+/// where its result cannot be represented.
+pub trait StackValue: Clone + Ord + fmt::Debug + fmt::Display + FromStr {
+    fn from_i64(n: i64) -> Self;
+    fn to_i64(&self) -> Option<i64>;
+    fn is_zero(&self) -> bool;
+    fn try_add(&self, rhs: &Self) -> Option<Self>;
+    fn try_sub(&self, rhs: &Self) -> Option<Self>;
+    fn try_mul(&self, rhs: &Self) -> Option<Self>;
+    /// Integer division, rounding towards zero. `None` when dividing by zero, or when the
+    /// quotient does not fit, as for `i64::MIN / -1`.
+    fn try_div(&self, rhs: &Self) -> Option<Self>;
+    /// The remainder of division, taking the sign of the divisor as the spec requires. `None`
+    /// when dividing by zero.
+    fn try_mod(&self, rhs: &Self) -> Option<Self>;
+    fn wrapped_add(&self, rhs: &Self) -> Self;
+    fn wrapped_sub(&self, rhs: &Self) -> Self;
+    fn wrapped_mul(&self, rhs: &Self) -> Self;
+    fn saturated_add(&self, rhs: &Self) -> Self;
+    fn saturated_sub(&self, rhs: &Self) -> Self;
+    fn saturated_mul(&self, rhs: &Self) -> Self;
+}
+
+/// What to do when the result of an `Add`, `Subtract` or `Multiply` does not fit in a `Value`.
+/// Reference interpreters disagree on this, so it is left up to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wrap around at the bounds of the value type
+    Wrap,
+    /// Clamp the result to the largest or smallest representable value
+    Saturate,
+    /// Ignore the command, leaving the stack untouched
+    #[default]
+    Ignore,
+    /// Stop the program with an error
+    Abort,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OverflowPolicy, String> {
+        match s {
+            "wrap" => Ok(OverflowPolicy::Wrap),
+            "saturate" => Ok(OverflowPolicy::Saturate),
+            "ignore" => Ok(OverflowPolicy::Ignore),
+            "abort" => Ok(OverflowPolicy::Abort),
+            _ => Err(format!("Unknown overflow policy: {}", s)),
+        }
+    }
+}
+
+#[cfg(not(feature = "bigint"))]
+pub type Value = i64;
+
+#[cfg(feature = "bigint")]
+pub type Value = BigInt;
+
+impl StackValue for i64 {
+    fn from_i64(n: i64) -> i64 {
+        n
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn try_add(&self, rhs: &i64) -> Option<i64> {
+        self.checked_add(*rhs)
+    }
+
+    fn try_sub(&self, rhs: &i64) -> Option<i64> {
+        self.checked_sub(*rhs)
+    }
+
+    fn try_mul(&self, rhs: &i64) -> Option<i64> {
+        self.checked_mul(*rhs)
+    }
+
+    fn try_div(&self, rhs: &i64) -> Option<i64> {
+        self.checked_div(*rhs)
+    }
+
+    fn try_mod(&self, rhs: &i64) -> Option<i64> {
+        if *rhs == 0 {
+            return None;
+        }
+        let rem = self.wrapping_rem(*rhs);
+        if rem != 0 && (rem < 0) != (*rhs < 0) {
+            Some(rem + rhs)
+        } else {
+            Some(rem)
+        }
+    }
+
+    fn wrapped_add(&self, rhs: &i64) -> i64 {
+        self.wrapping_add(*rhs)
+    }
+
+    fn wrapped_sub(&self, rhs: &i64) -> i64 {
+        self.wrapping_sub(*rhs)
+    }
+
+    fn wrapped_mul(&self, rhs: &i64) -> i64 {
+        self.wrapping_mul(*rhs)
+    }
+
+    fn saturated_add(&self, rhs: &i64) -> i64 {
+        self.saturating_add(*rhs)
+    }
+
+    fn saturated_sub(&self, rhs: &i64) -> i64 {
+        self.saturating_sub(*rhs)
+    }
+
+    fn saturated_mul(&self, rhs: &i64) -> i64 {
+        self.saturating_mul(*rhs)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl StackValue for BigInt {
+    fn from_i64(n: i64) -> BigInt {
+        BigInt::from(n)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        ToPrimitive::to_i64(self)
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+
+    fn try_add(&self, rhs: &BigInt) -> Option<BigInt> {
+        Some(self + rhs)
+    }
+
+    fn try_sub(&self, rhs: &BigInt) -> Option<BigInt> {
+        Some(self - rhs)
+    }
+
+    fn try_mul(&self, rhs: &BigInt) -> Option<BigInt> {
+        Some(self * rhs)
+    }
+
+    fn try_div(&self, rhs: &BigInt) -> Option<BigInt> {
+        if Zero::is_zero(rhs) {
+            None
+        } else {
+            Some(self / rhs)
+        }
+    }
+
+    fn try_mod(&self, rhs: &BigInt) -> Option<BigInt> {
+        if Zero::is_zero(rhs) {
+            None
+        } else {
+            Some(self.mod_floor(rhs))
+        }
+    }
+
+    fn wrapped_add(&self, rhs: &BigInt) -> BigInt {
+        self + rhs
+    }
+
+    fn wrapped_sub(&self, rhs: &BigInt) -> BigInt {
+        self - rhs
+    }
+
+    fn wrapped_mul(&self, rhs: &BigInt) -> BigInt {
+        self * rhs
+    }
+
+    fn saturated_add(&self, rhs: &BigInt) -> BigInt {
+        self + rhs
+    }
+
+    fn saturated_sub(&self, rhs: &BigInt) -> BigInt {
+        self - rhs
+    }
+
+    fn saturated_mul(&self, rhs: &BigInt) -> BigInt {
+        self * rhs
+    }
+}
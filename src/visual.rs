@@ -0,0 +1,101 @@
+//! Draws a running program in a truecolor terminal, two codels per character cell
+
+use std::io::{self, BufRead, Write};
+
+use ansi_term::Colour::RGB;
+
+use interpreter::Interpreter;
+use state::{Direction, Position};
+use util;
+
+/// How many values of the stack and lines of output are shown below the program.
+const SHOWN_STACK_VALUES: usize = 32;
+const SHOWN_OUTPUT_LINES: usize = 5;
+
+/// Replaces whatever `out` showed with `frame`. Rather than clearing the whole terminal first,
+/// which flickers, every line is drawn over and then cleared to its end.
+pub fn redraw<W: Write>(out: &mut W, frame: &str) -> io::Result<()> {
+    write!(out, "\x1b[H{}\x1b[J", frame.replace('\n', "\x1b[K\n"))?;
+    out.flush()
+}
+
+fn arrow(dir: Direction) -> char {
+    match dir {
+        Direction::Right => '→',
+        Direction::Down => '↓',
+        Direction::Left => '←',
+        Direction::Up => '↑',
+    }
+}
+
+/// The CC is left or right relative to the DP, so unlike the DP it is not drawn as an arrow.
+fn cc_name(cc: Direction) -> &'static str {
+    match cc {
+        Direction::Left => "left",
+        _ => "right",
+    }
+}
+
+/// Renders the program as the interpreter currently sees it, followed by the DP and CC, the
+/// stack and the last lines of `output`. Blocks other than the current one are dimmed, and the
+/// codel the current block will be left through is drawn in its inverse color.
+pub fn render_frame<R: BufRead, W: Write>(interp: &Interpreter<R, W>, output: &str) -> String {
+    let program = interp.program();
+    let state = interp.state();
+    let (width, height) = program.image().dimensions();
+    let current = program.block_id(state.pos);
+    let exit = program.exit_from(state.pos, state.dp(), state.cc());
+    let codel_color = |pos: Position| {
+        let (r, g, b) = program.block_at(pos).color.rgb_color;
+        if exit.is_some_and(|exit| exit.from == pos) {
+            RGB(255 - r, 255 - g, 255 - b)
+        } else if program.block_id(pos) == current {
+            RGB(r, g, b)
+        } else {
+            let dimmed = util::mix((r, g, b), (0, 0, 0), 0.45);
+            RGB(dimmed[0], dimmed[1], dimmed[2])
+        }
+    };
+
+    let mut frame = String::new();
+    for top in (0..height).step_by(2) {
+        for left in 0..width {
+            let upper = codel_color(Position { left, top });
+            let cell = if top + 1 < height {
+                upper.on(codel_color(Position { left, top: top + 1 })).paint("▀")
+            } else {
+                upper.paint("▀")
+            };
+            frame.push_str(&cell.to_string());
+        }
+        frame.push('\n');
+    }
+
+    let blk = program.block(current);
+    frame.push_str(&format!("Step {}  DP {}  CC {}  at {} in {} ({} codels)",
+                            interp.steps(),
+                            arrow(state.dp()),
+                            cc_name(state.cc()),
+                            state.pos,
+                            blk.color,
+                            blk.size));
+    match interp.next_command() {
+        Some(cmd) => frame.push_str(&format!("  next: {}\n", cmd)),
+        None => frame.push_str("  terminated\n"),
+    }
+
+    frame.push_str("Stack (top first):");
+    for value in state.stack.iter().rev().take(SHOWN_STACK_VALUES) {
+        frame.push_str(&format!(" {}", value));
+    }
+    if state.stack.len() > SHOWN_STACK_VALUES {
+        frame.push_str(&format!(" ... {} more", state.stack.len() - SHOWN_STACK_VALUES));
+    }
+    frame.push_str("\nOutput:\n");
+    let lines: Vec<&str> = output.lines().collect();
+    for line in &lines[lines.len().saturating_sub(SHOWN_OUTPUT_LINES)..] {
+        frame.push_str(line);
+        frame.push('\n');
+    }
+    frame
+}
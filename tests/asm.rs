@@ -0,0 +1,50 @@
+extern crate piet;
+
+use piet::{Command, DataType, Interpreter};
+use piet::asm::{self, Instruction};
+
+fn run(src: &str) -> String {
+    let img = asm::assemble(&asm::parse(src).unwrap()).unwrap();
+    let mut interp = Interpreter::with_io(img, &b"7"[..], Vec::new()).unwrap();
+    interp.set_max_steps(Some(10_000));
+    interp.run().unwrap();
+    String::from_utf8(interp.output().clone()).unwrap()
+}
+
+#[test]
+fn parses_instructions_and_comments() {
+    let instrs = asm::parse("# comment\n\npush -3\nout char ; trailing\nIN(NUMBER)\ndup\n")
+        .unwrap();
+    assert_eq!(instrs,
+               vec![Instruction::Push(-3),
+                    Instruction::Op(Command::Out(DataType::Char)),
+                    Instruction::Op(Command::In(DataType::Number)),
+                    Instruction::Op(Command::Duplicate)]);
+
+    assert_eq!(asm::parse("pop\nfrobnicate").unwrap_err(),
+               "line 2: Unknown command: frobnicate");
+    assert_eq!(asm::parse("push").unwrap_err(), "line 1: push requires a value");
+    assert_eq!(asm::parse("push ten").unwrap_err(), "line 1: Invalid value: ten");
+    assert_eq!(asm::parse("add 1 2").unwrap_err(), "line 1: Unexpected argument: 2");
+}
+
+#[test]
+fn runs_assembled_programs_to_the_end() {
+    assert_eq!(run("push 72\nout char\npush 105\nout char"), "Hi");
+    assert_eq!(run(""), "");
+    assert_eq!(run("in number\npush 6\nmul\nout number"), "42");
+}
+
+#[test]
+fn pushes_any_value() {
+    let values = [0, 1, 64, 65, 4096, 1_000_003, -1, -5000];
+    let src: String = values.iter()
+        .map(|n| format!("push {}\nout number\npush 32\nout char\n", n))
+        .collect();
+    assert_eq!(run(&src), "0 1 64 65 4096 1000003 -1 -5000 ");
+}
+
+#[test]
+fn passes_through_white_for_nop() {
+    assert_eq!(run("nop\npush 3\nnop\nnop\ndup\nadd\nnop\nout number\nnop"), "6");
+}
@@ -0,0 +1,55 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use piet::Command;
+use piet::cfg::{Cfg, Format};
+use piet::program::Program;
+use piet::state::Direction;
+
+fn row(codels: &str) -> Program {
+    Program::new(common::codels(&[codels])).unwrap()
+}
+
+fn dot(program: &Program) -> String {
+    let mut out = Vec::new();
+    Cfg::new(program).write(program, Format::Dot, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn fans_out_on_pointer() {
+    // Red then dark cyan: pointer
+    let program = row("R-C");
+    let cfg = Cfg::new(&program);
+    let edges: Vec<_> = cfg.edges()
+        .iter()
+        .filter(|edge| edge.from == (0, Direction::Right, Direction::Left))
+        .map(|edge| (edge.to, edge.cmd, edge.turns))
+        .collect();
+    assert_eq!(edges,
+               vec![((1, Direction::Right, Direction::Left), Command::Pointer, 0),
+                    ((1, Direction::Down, Direction::Left), Command::Pointer, 1),
+                    ((1, Direction::Left, Direction::Left), Command::Pointer, 2),
+                    ((1, Direction::Up, Direction::Left), Command::Pointer, 3)]);
+    assert!(dot(&program).contains("b0_rl -> b1_ul [label=\"pointer 3\"];"));
+}
+
+#[test]
+fn marks_white_slides_and_terminal_nodes() {
+    let program = row("RW-R");
+    let graph = dot(&program);
+    assert!(graph.starts_with("digraph piet {\n"));
+    assert!(graph.contains("start -> b0_rl;"));
+    assert!(graph.contains("b0_rl -> b2_rl [label=\"white\", style=dashed];"));
+    assert!(graph.contains("b2_rl -> b0_ll [label=\"white\", style=dashed];"));
+
+    let program = row("R");
+    let cfg = Cfg::new(&program);
+    assert_eq!(cfg.nodes(), &[(0, Direction::Right, Direction::Left)]);
+    assert!(cfg.edges().is_empty());
+    assert!(cfg.is_terminal((0, Direction::Right, Direction::Left)));
+    assert!(dot(&program)
+        .contains("b0_rl [label=\"block 0\\nr,l\", fillcolor=\"#ff0000\", peripheries=2];"));
+}
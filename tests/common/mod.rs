@@ -0,0 +1,55 @@
+//! Fixtures shared between integration tests
+
+// Each test crate uses only some of the fixtures
+#![allow(dead_code)]
+
+use image::{Rgb, RgbImage};
+
+/// Builds an image from rows of codels: `W`hite, blac`K` and the hues `R`ed, `Y`ellow,
+/// `G`reen, `C`yan, `B`lue and `M`agenta, which a `+` before makes light and a `-` dark.
+pub fn codels(rows: &[&str]) -> RgbImage {
+    let rows: Vec<Vec<[u8; 3]>> = rows.iter().map(|row| parse_row(row)).collect();
+    RgbImage::from_fn(rows[0].len() as u32,
+                      rows.len() as u32,
+                      |x, y| Rgb(rows[y as usize][x as usize]))
+}
+
+fn parse_row(row: &str) -> Vec<[u8; 3]> {
+    let mut codels = Vec::new();
+    let mut lightness = None;
+    for c in row.chars() {
+        // The channels of a hue that are on and off
+        let (on, off) = match lightness.take() {
+            Some('+') => (255, 192),
+            Some(_) => (192, 0),
+            None => (255, 0),
+        };
+        codels.push(match c {
+            '+' | '-' => {
+                lightness = Some(c);
+                continue;
+            }
+            'W' => [255, 255, 255],
+            'K' => [0, 0, 0],
+            'R' => [on, off, off],
+            'Y' => [on, on, off],
+            'G' => [off, on, off],
+            'C' => [off, on, on],
+            'B' => [off, off, on],
+            'M' => [on, off, on],
+            c => panic!("Unknown codel {}", c),
+        });
+    }
+    codels
+}
+
+/// Light red, red and dark red, twice: pushes a value on each of its first five steps, then
+/// bounces back and forth forever.
+pub fn pushing_row() -> RgbImage {
+    codels(&["+RR-R+RR-R"])
+}
+
+/// Red, dark red, then magenta: push 1, then out(char), then bouncing between those blocks.
+pub fn push_then_out_char() -> RgbImage {
+    codels(&["R-RM"])
+}
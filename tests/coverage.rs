@@ -0,0 +1,30 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use piet::Interpreter;
+use piet::coverage::Coverage;
+use piet::state::Position;
+
+#[test]
+fn compares_executed_blocks_with_reachable_ones() {
+    // `common::push_then_out_char`, over a green block which is never reachable
+    let img = common::codels(&["R-RM", "GKK"]);
+    let mut interp = Interpreter::with_io(img, &b""[..], Vec::new()).unwrap();
+    let mut coverage = Coverage::new(interp.program());
+    let step = interp.step().unwrap().unwrap();
+    coverage.record(interp.program(), &step);
+
+    let program = interp.program();
+    assert!(coverage.is_covered(program.block_id(Position { left: 1, top: 0 })));
+    assert!(!coverage.is_covered(program.block_id(Position { left: 2, top: 0 })));
+    assert_eq!(coverage.blocks(), (2, 3));
+    assert_eq!(coverage.edges().0, 1);
+
+    let mut summary = Vec::new();
+    coverage.write_summary(program, &mut summary).unwrap();
+    let summary = String::from_utf8(summary).unwrap();
+    assert!(summary.starts_with("Blocks covered: 2 of 3 reachable (66.7%)\n"));
+    assert!(summary.contains("Never entered: Magenta block at (2, 0)"));
+}
@@ -0,0 +1,56 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use piet::{Command, DataType, Interpreter};
+use piet::debugger::{Breakpoint, Debugger};
+use piet::state::Position;
+
+fn debugger() -> Debugger<&'static [u8], Vec<u8>> {
+    Debugger::new(Interpreter::with_io(common::pushing_row(), &b""[..], Vec::new()).unwrap())
+}
+
+fn execute(debugger: &mut Debugger<&[u8], Vec<u8>>, line: &str) -> String {
+    let mut out = Vec::new();
+    assert!(debugger.execute(line, &mut out).unwrap());
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn parses_breakpoints() {
+    assert_eq!("3, 4".parse(),
+               Ok(Breakpoint::Position(Position { left: 3, top: 4 })));
+    assert_eq!("out(char)".parse(),
+               Ok(Breakpoint::Command(Command::Out(DataType::Char))));
+    assert!("3,x".parse::<Breakpoint>().is_err());
+}
+
+#[test]
+fn steps_and_prints_the_stack() {
+    let mut dbg = debugger();
+    execute(&mut dbg, "step 2");
+    assert_eq!(dbg.interpreter().steps(), 2);
+    assert_eq!(execute(&mut dbg, "print stack"), "[1, 1]\n");
+    assert_eq!(execute(&mut dbg, "print dp"), "Right\n");
+}
+
+#[test]
+fn continues_until_a_breakpoint() {
+    let mut dbg = debugger();
+    execute(&mut dbg, "break 4,0");
+    execute(&mut dbg, "continue");
+    assert_eq!(dbg.interpreter().state().pos, Position { left: 4, top: 0 });
+
+    // Moving back from the dark red end of the row into red pops
+    execute(&mut dbg, "break pop");
+    execute(&mut dbg, "continue");
+    assert_eq!(dbg.interpreter().state().pos, Position { left: 5, top: 0 });
+    assert_eq!(dbg.interpreter().next_command(), Some(Command::Pop));
+}
+
+#[test]
+fn quits() {
+    let mut dbg = debugger();
+    assert!(!dbg.execute("quit", &mut Vec::new()).unwrap());
+}
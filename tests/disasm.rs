@@ -0,0 +1,45 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use piet::disasm;
+use piet::program::Program;
+
+fn listing(program: &Program, id: usize) -> String {
+    let mut out = Vec::new();
+    disasm::write_block(&mut out, program, id).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn lists_every_exit_of_a_block() {
+    let program = Program::new(common::codels(&["R-RWM"])).unwrap();
+
+    assert_eq!(listing(&program, 0),
+               "block 0: Red, 1 codel at (0, 0)\n  \
+                r,l  exit (0, 0) -> block 1 at (1, 0): push\n  \
+                r,r  exit (0, 0) -> block 1 at (1, 0): push\n  \
+                d,l  exit (0, 0) as r,r -> block 1 at (1, 0): push\n  \
+                d,r  exit (0, 0) as r,l -> block 1 at (1, 0): push\n  \
+                l,l  exit (0, 0) as r,l -> block 1 at (1, 0): push\n  \
+                l,r  exit (0, 0) as r,r -> block 1 at (1, 0): push\n  \
+                u,l  exit (0, 0) as r,r -> block 1 at (1, 0): push\n  \
+                u,r  exit (0, 0) as r,l -> block 1 at (1, 0): push\n");
+    assert!(listing(&program, 1)
+        .contains("  r,l  exit (1, 0) -> white -> block 3 at (3, 0): nop\n  \
+                   r,r  exit (1, 0) -> white -> block 3 at (3, 0): nop\n  \
+                   d,l  exit (1, 0) as l,r -> block 0 at (0, 0): pop\n"));
+    assert_eq!(listing(&program, 2), "block 2: White, 1 codel at (2, 0)\n");
+}
+
+#[test]
+fn lists_terminal_blocks() {
+    // A single red codel has nowhere to go
+    let program = Program::new(common::codels(&["R"])).unwrap();
+    let mut out = Vec::new();
+    disasm::write_listing(&mut out, &program).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().count(), 9);
+    assert!(out.lines().skip(1).all(|line| line.ends_with("no exit, the program terminates")));
+}
@@ -0,0 +1,153 @@
+extern crate image;
+extern crate piet;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use image::RgbImage;
+
+use piet::{Interpreter, OverflowPolicy};
+use piet::asm;
+use piet::emit::{self, Options, Target};
+use piet::lang;
+use piet::layout;
+use piet::program::Program;
+use piet::reader;
+
+/// Compiles `img` to C, builds it with the system C compiler and runs it on `input`.
+fn run_c(name: &str, img: RgbImage, opts: &Options, input: &str) -> Output {
+    let dir = env::temp_dir();
+    let src = dir.join(format!("piet-emit-{}-{}.c", name, std::process::id()));
+    let exe: PathBuf = src.with_extension("out");
+    let program = Program::new(img).unwrap();
+    let mut file = File::create(&src).unwrap();
+    emit::write(&program, Target::C, opts, &mut file).unwrap();
+    drop(file);
+
+    let built = Command::new("cc")
+        .arg("-std=c99")
+        .arg("-o")
+        .arg(&exe)
+        .arg(&src)
+        .status()
+        .expect("These tests need a C compiler named cc");
+    assert!(built.success(), "{} did not compile", src.display());
+    let mut child = Command::new(&exe)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_file(&src);
+    let _ = std::fs::remove_file(&exe);
+    output
+}
+
+fn interpret(img: RgbImage, input: &str) -> String {
+    let mut interp = Interpreter::with_io(img, input.as_bytes(), Vec::new()).unwrap();
+    interp.set_max_steps(Some(1_000_000));
+    interp.run().unwrap();
+    String::from_utf8(interp.output().clone()).unwrap()
+}
+
+/// Checks that the compiled program writes the same output as the interpreter.
+fn assert_matches(name: &str, img: RgbImage, input: &str) {
+    let expected = interpret(img.clone(), input);
+    let output = run_c(name, img, &Options::default(), input);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected, "{}", name);
+}
+
+#[test]
+fn matches_the_interpreter_on_samples() {
+    for &(name, input) in &[("Piet_hello.png", ""),
+                            ("alpha_filled.png", ""),
+                            ("piet_pi.png", ""),
+                            ("99bottles.png", ""),
+                            ("artistic_hello.gif", ""),
+                            ("cowsay.png", "Moo, said the Piet\n"),
+                            ("hanoi.gif", "3\n")] {
+        let img = reader::read(format!("testdata/{}", name), &reader::Options::default())
+            .unwrap();
+        assert_matches(name, img, input);
+    }
+}
+
+#[test]
+fn matches_the_interpreter_on_edge_cases() {
+    let src = "push -7\npush 3\nmod\nout number\n
+               push 7\npush -3\nmod\nout number\n
+               push -7\npush 2\ndiv\nout number\n
+               push 5\npush 0\ndiv\npush 0\nmod\nout number\n
+               push 1\npush 2\npush 3\npush 4\npush 3\npush -1\nroll\n
+               out number\nout number\nout number\nout number\n
+               push 9\npush 5\npush 1\nroll\npop\nnot\nout number\n
+               push -1\nout char\npush 55296\nout char\npush 233\nout char\n
+               in number\nout number\nin number\nout number\nin number\nout number\n
+               in char\nout char\nin char\nout number\nin char\nout char\nin char\n
+               pop\npop\npop\nadd\nout number\npush 3\npush 2\ngreater\nout number";
+    let img = asm::assemble(&asm::parse(src).unwrap()).unwrap();
+    assert_matches("edge-cases", img, " \t-12+7 -x\u{a0}\u{e9}");
+}
+
+#[test]
+fn matches_the_interpreter_on_characters_out_of_range() {
+    // 2^32 + 65 would print 'A' if cut down to 32 bits
+    let src = "push 1114112\nout char\npush 4294967361\nout char\npush 66\nout char";
+    let img = asm::assemble(&asm::parse(src).unwrap()).unwrap();
+    assert_eq!(interpret(img.clone(), ""), "B");
+    assert_matches("out-of-range-chars", img, "");
+}
+
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn matches_the_interpreter_on_numbers_left_unread() {
+    // The first two numbers do not fit, so each in(number) leaves them for the in(char) after
+    // it, as it does the sign at the end of the input
+    let src = "in number\nin char\nout char\nin number\nin char\nout char\n
+               in number\nin char\nout char\nout number\nin number\nin char\nout char";
+    let img = asm::assemble(&asm::parse(src).unwrap()).unwrap();
+    let input = " 99999999999999999999 -";
+    assert_eq!(interpret(img.clone(), input), format!("99 {}-", "9".repeat(18)));
+    assert_matches("unread-numbers", img, input);
+}
+
+#[test]
+fn matches_the_interpreter_on_branches() {
+    let src = "let i = 0;
+               let n = 0;
+               input n;
+               while i < n {
+                   if i % 2 == 0 { print i; } else { print \"-\"; }
+                   i = i + 1;
+               }";
+    let img = layout::render(&lang::compile(&lang::parse(src).unwrap()));
+    assert_matches("branches", img, "7");
+}
+
+#[test]
+fn handles_overflow_by_policy() {
+    // 2^62 + 2^62 overflows, as does its square
+    let src = "push 4611686018427387904\ndup\nadd\nout number\n
+               push 4611686018427387904\ndup\nmul\nout number";
+    let img = asm::assemble(&asm::parse(src).unwrap()).unwrap();
+    let expected = [(OverflowPolicy::Ignore, "46116860184273879044611686018427387904"),
+                    (OverflowPolicy::Wrap, "-92233720368547758080"),
+                    (OverflowPolicy::Saturate, "92233720368547758079223372036854775807")];
+    for &(overflow, stdout) in &expected {
+        let opts = Options { overflow };
+        let output = run_c("overflow", img.clone(), &opts, "");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), stdout, "{:?}", overflow);
+    }
+
+    let opts = Options { overflow: OverflowPolicy::Abort };
+    let output = run_c("overflow-abort", img, &opts, "");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: Arithmetic overflow in Add at ("));
+}
@@ -0,0 +1,18 @@
+extern crate image;
+extern crate piet;
+
+use image::{Rgb, RgbImage};
+
+use piet::color_block::ColorBlock;
+use piet::state::{Direction, Position};
+
+#[test]
+fn fills_huge_single_color_block_without_overflowing_the_stack() {
+    let img = RgbImage::from_pixel(2000, 2000, Rgb([255, 0, 0]));
+    let blk = ColorBlock::from_position_in_img(&img, &Position { left: 1000, top: 1000 }).unwrap();
+    assert_eq!(blk.value(), 4_000_000);
+    assert_eq!(blk.boundary_codel_position(&Direction::Right, &Direction::Left),
+               Position { left: 1999, top: 0 });
+    assert_eq!(blk.boundary_codel_position(&Direction::Up, &Direction::Right),
+               Position { left: 1999, top: 0 });
+}
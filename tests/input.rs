@@ -0,0 +1,92 @@
+extern crate piet;
+
+use std::io::BufReader;
+
+use piet::{StackValue, Value};
+use piet::input::{read_char, read_number, Pushback};
+
+fn number(n: i64) -> Option<Value> {
+    Some(Value::from_i64(n))
+}
+
+#[test]
+fn reads_one_unicode_scalar_at_a_time() {
+    let mut input = "aé€😀".as_bytes();
+    assert_eq!(read_char(&mut input).unwrap(), Some('a'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('é'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('€'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('😀'));
+    assert_eq!(read_char(&mut input).unwrap(), None);
+
+    // A truncated sequence and a stray continuation byte are each decoded as U+FFFD, leaving
+    // the byte that ended the sequence unread
+    let mut input = &b"\xe2\x82x\x80y"[..];
+    assert_eq!(read_char(&mut input).unwrap(), Some('\u{fffd}'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('x'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('\u{fffd}'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('y'));
+}
+
+#[test]
+fn skips_leading_whitespace_before_numbers() {
+    let mut input = Pushback::new(&b" \t\r\n 42\n-7 +3"[..]);
+    assert_eq!(read_number(&mut input).unwrap(), number(42));
+    assert_eq!(read_number(&mut input).unwrap(), number(-7));
+    assert_eq!(read_number(&mut input).unwrap(), number(3));
+    assert_eq!(read_number(&mut input).unwrap(), None);
+
+    // Bytes of a UTF-8 sequence are not whitespace, even if the code point they equal is
+    let mut input = Pushback::new("\u{a0}5".as_bytes());
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    assert_eq!(read_char(&mut input).unwrap(), Some('\u{a0}'));
+}
+
+#[test]
+fn leaves_unread_input_for_the_next_in() {
+    let mut input = Pushback::new(&b"12ab 34"[..]);
+    assert_eq!(read_number(&mut input).unwrap(), number(12));
+    assert_eq!(read_char(&mut input).unwrap(), Some('a'));
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    assert_eq!(read_char(&mut input).unwrap(), Some('b'));
+    assert_eq!(read_number(&mut input).unwrap(), number(34));
+
+    // A sign without a digit after it stays in the input, even at the end of it
+    let mut input = Pushback::new(&b" -x +"[..]);
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    assert_eq!(read_char(&mut input).unwrap(), Some('-'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('x'));
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    assert_eq!(read_char(&mut input).unwrap(), Some('+'));
+    assert_eq!(read_char(&mut input).unwrap(), None);
+}
+
+#[test]
+fn leaves_unread_input_across_buffer_refills() {
+    // A buffer of a single byte means every byte looked at after the first is a refill
+    let mut input = Pushback::new(BufReader::with_capacity(1, &b"-\n+"[..]));
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    assert_eq!(read_char(&mut input).unwrap(), Some('-'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('\n'));
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    assert_eq!(read_char(&mut input).unwrap(), Some('+'));
+
+    let mut input = Pushback::new(BufReader::with_capacity(1, &b" +17x"[..]));
+    assert_eq!(read_number(&mut input).unwrap(), number(17));
+    assert_eq!(read_char(&mut input).unwrap(), Some('x'));
+}
+
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn leaves_numbers_that_do_not_fit_unread() {
+    let mut input = Pushback::new(BufReader::with_capacity(4, &b" -99999999999999999999 7"[..]));
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    assert_eq!(read_char(&mut input).unwrap(), Some('-'));
+    assert_eq!(read_char(&mut input).unwrap(), Some('9'));
+    // The rest still does not fit
+    assert_eq!(read_number(&mut input).unwrap(), None);
+    for _ in 0..19 {
+        assert_eq!(read_char(&mut input).unwrap(), Some('9'));
+    }
+    assert_eq!(read_number(&mut input).unwrap(), number(7));
+}
@@ -0,0 +1,85 @@
+extern crate piet;
+
+use piet::Interpreter;
+use piet::lang;
+use piet::layout;
+
+fn run(src: &str, input: &str) -> String {
+    let img = layout::render(&lang::compile(&lang::parse(src).unwrap()));
+    let mut interp = Interpreter::with_io(img, input.as_bytes(), Vec::new()).unwrap();
+    interp.set_max_steps(Some(1_000_000));
+    interp.run().unwrap();
+    String::from_utf8(interp.output().clone()).unwrap()
+}
+
+#[test]
+fn loops_over_variables() {
+    let src = "# Fibonacci
+               let a = 0;
+               let b = 1;
+               let n = 10;
+               while n > 0 {
+                   print a;
+                   print \" \";
+                   let t = a + b;
+                   a = b;
+                   b = t;
+                   n = n - 1;
+               }";
+    assert_eq!(run(src, ""), "0 1 1 2 3 5 8 13 21 34 ");
+}
+
+#[test]
+fn branches_on_conditions() {
+    let src = "let i = 1;
+               while i <= 15 {
+                   if i % 15 == 0 {
+                       print \"FizzBuzz\";
+                   } else if i % 3 == 0 {
+                       print \"Fizz\";
+                   } else if i % 5 == 0 {
+                       print \"Buzz\";
+                   } else {
+                       print i;
+                   }
+                   printc ',';
+                   i = i + 1;
+               }";
+    assert_eq!(run(src, ""),
+               "1,2,Fizz,4,Buzz,Fizz,7,8,Fizz,Buzz,11,Fizz,13,14,FizzBuzz,");
+}
+
+#[test]
+fn nests_loops_and_reads_input() {
+    let src = "let c = 0;
+               inputc c;
+               while c != '.' {
+                   let n = 0;
+                   while n < 2 {
+                       if c >= 'a' && c <= 'z' { printc c - 32; } else { printc c; }
+                       n = n + 1;
+                   }
+                   inputc c;
+               }
+               let x = 0;
+               input x;
+               if !(x < 0) || 0 { print -x * 3 / 2; print 17 % -5; }
+               if x { } else { print 1; }";
+    assert_eq!(run(src, "ab,Z.7"), "AABB,,ZZ-10-3");
+    assert_eq!(run("", ""), "");
+    assert_eq!(run("while 0 { }", ""), "");
+}
+
+#[test]
+fn reports_errors_with_line_numbers() {
+    let err = |src| lang::parse(src).unwrap_err();
+    assert_eq!(err("let x = 1;\nprint y;"), "line 2: Undeclared variable: y");
+    assert_eq!(err("let x = 1;\nlet x = 2;"), "line 2: Variable declared twice: x");
+    assert_eq!(err("let x = x;"), "line 1: Undeclared variable: x");
+    assert_eq!(err("let while = 1;"),
+               "line 1: Expected a variable name, found `while`");
+    assert_eq!(err("print 1"), "at end of input: Expected `;`");
+    assert_eq!(err("if 1 {\nprint 2;"), "at end of input: Expected `}`");
+    assert_eq!(err("print 'ab';"), "line 1: Invalid character literal: 'ab'");
+    assert_eq!(err("print 1 $ 2;"), "line 1: Unexpected character: $");
+}
@@ -0,0 +1,46 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use std::time::Duration;
+
+use piet::{Interpreter, PietError};
+
+fn run<F>(configure: F) -> PietError
+    where F: FnOnce(&mut Interpreter<&[u8], Vec<u8>>)
+{
+    let mut interp = Interpreter::with_io(common::pushing_row(), &b""[..], Vec::new()).unwrap();
+    configure(&mut interp);
+    interp.run().unwrap_err()
+}
+
+#[test]
+fn stops_after_the_step_limit() {
+    match run(|interp| interp.set_max_steps(Some(2))) {
+        PietError::StepLimitExceeded { limit, state } => {
+            assert_eq!(limit, 2);
+            assert_eq!(state.stack.len(), 2);
+        }
+        err => panic!("Unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn stops_after_the_timeout() {
+    match run(|interp| interp.set_timeout(Some(Duration::from_millis(10)))) {
+        PietError::Timeout { limit, .. } => assert_eq!(limit, Duration::from_millis(10)),
+        err => panic!("Unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn stops_when_the_stack_grows_past_its_limit() {
+    match run(|interp| interp.set_max_stack(Some(3))) {
+        PietError::StackLimitExceeded { limit, state } => {
+            assert_eq!(limit, 3);
+            assert_eq!(state.stack.len(), 4);
+        }
+        err => panic!("Unexpected error: {}", err),
+    }
+}
@@ -0,0 +1,64 @@
+#![cfg(not(feature = "bigint"))]
+
+extern crate image;
+extern crate piet;
+
+use image::RgbImage;
+
+use piet::{Command, Interpreter, OverflowPolicy, PietError};
+use piet::asm;
+
+/// Adds 1 to `i64::MAX`, subtracts 1 from `i64::MIN` and doubles `i64::MAX`. Only positive
+/// values are pushed, as the assembler builds negative ones with `Subtract`.
+fn overflowing_program() -> RgbImage {
+    let src = "push 9223372036854775807\npush 1\nadd\n
+               push 0\npush 9223372036854775807\nsub\npush 1\nsub\npush 1\nsub\n
+               push 9223372036854775807\npush 2\nmul";
+    asm::assemble(&asm::parse(src).unwrap()).unwrap()
+}
+
+fn run(policy: OverflowPolicy) -> Vec<i64> {
+    let mut interp = Interpreter::with_io(overflowing_program(), &b""[..], Vec::new()).unwrap();
+    interp.set_overflow_policy(policy);
+    interp.run().unwrap();
+    interp.state().stack.clone()
+}
+
+#[test]
+fn wraps_around() {
+    assert_eq!(run(OverflowPolicy::Wrap), [i64::MIN, i64::MAX, -2]);
+}
+
+#[test]
+fn saturates() {
+    assert_eq!(run(OverflowPolicy::Saturate), [i64::MAX, i64::MIN, i64::MAX]);
+}
+
+#[test]
+fn ignores_overflowing_commands() {
+    assert_eq!(run(OverflowPolicy::Ignore), [i64::MAX, 1, i64::MIN, 1, i64::MAX, 2]);
+}
+
+#[test]
+fn aborts_without_moving_on() {
+    let mut interp = Interpreter::with_io(overflowing_program(), &b""[..], Vec::new()).unwrap();
+    interp.set_overflow_policy(OverflowPolicy::Abort);
+    let snapshot = |interp: &Interpreter<&[u8], Vec<u8>>| {
+        let state = interp.state();
+        (state.stack.clone(), state.pos, state.dp(), state.cc())
+    };
+    let mut before = snapshot(&interp);
+    while let Ok(Some(_)) = interp.step() {
+        before = snapshot(&interp);
+    }
+    assert_eq!(before.0, [i64::MAX, 1]);
+    // The failed step can be retried, as neither the stack nor the position has changed
+    for _ in 0..2 {
+        assert_eq!(snapshot(&interp), before);
+        match interp.step() {
+            Err(PietError::Overflow { cmd: Command::Add, .. }) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+    assert_eq!(snapshot(&interp), before);
+}
@@ -0,0 +1,53 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use piet::{Command, Interpreter};
+use piet::profile::Profile;
+use piet::state::Position;
+
+#[test]
+fn counts_block_entries_transitions_and_commands() {
+    // Red and dark red, bouncing back and forth between pushes and pops
+    let mut interp = Interpreter::with_io(common::codels(&["R-R"]), &b""[..], Vec::new())
+        .unwrap();
+    let mut profile = Profile::new(interp.program());
+    for _ in 0..5 {
+        let step = interp.step().unwrap().unwrap();
+        profile.record(interp.program(), &step);
+    }
+
+    let program = interp.program();
+    let red = program.block_id(Position { left: 0, top: 0 });
+    let dark_red = program.block_id(Position { left: 1, top: 0 });
+    assert_eq!(profile.entries(red), 3);
+    assert_eq!(profile.entries(dark_red), 3);
+    assert_eq!(profile.transitions(red, dark_red), 3);
+    assert_eq!(profile.transitions(dark_red, red), 2);
+    assert_eq!(profile.command_counts(),
+               vec![(Command::Push, 3), (Command::Pop, 2)]);
+
+    let heatmap = profile.heatmap(program, 4);
+    assert_eq!(heatmap.dimensions(), (8, 4));
+}
+
+#[test]
+fn leaves_white_out_of_the_histogram() {
+    // The program starts by sliding into red, then bounces between red and green through white
+    let mut interp = Interpreter::with_io(common::codels(&["WRWG"]), &b""[..], Vec::new())
+        .unwrap();
+    let mut profile = Profile::new(interp.program());
+    for _ in 0..3 {
+        let step = interp.step().unwrap().unwrap();
+        profile.record(interp.program(), &step);
+    }
+
+    let program = interp.program();
+    let red = program.block_id(Position { left: 1, top: 0 });
+    let green = program.block_id(Position { left: 3, top: 0 });
+    assert_eq!(profile.entries(red), 2);
+    assert_eq!(profile.entries(green), 1);
+    assert_eq!(profile.transitions(red, green), 1);
+    assert!(profile.command_counts().is_empty());
+}
@@ -0,0 +1,57 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use image::{Rgb, RgbImage};
+
+use piet::program::Program;
+use piet::state::{Direction, Position};
+
+fn program(rows: &[&str]) -> Program {
+    Program::new(common::codels(rows)).unwrap()
+}
+
+#[test]
+fn labels_every_codel_with_its_block() {
+    let prog = program(&["RRG", "RGG", "KKG"]);
+    assert_eq!(prog.blocks().len(), 3);
+    let red = prog.block_at(Position { left: 1, top: 0 });
+    assert_eq!(red.color.mnemonic, "Red");
+    assert_eq!(red.size, 3);
+    assert_eq!(prog.block_id(Position { left: 0, top: 1 }), 0);
+    assert_eq!(prog.block_id(Position { left: 2, top: 2 }),
+               prog.block_id(Position { left: 1, top: 1 }));
+}
+
+#[test]
+fn precomputes_exits_for_every_dp_and_cc() {
+    let prog = program(&["RRG", "RGG", "KKG"]);
+    let red = prog.block(0);
+    let exit = red.exit(Direction::Right, Direction::Left).unwrap();
+    assert_eq!(exit.from, Position { left: 1, top: 0 });
+    assert_eq!(exit.to, Position { left: 2, top: 0 });
+    assert!(!exit.through_white);
+
+    // Down from the red block is black, and left and up are the edge of the program, so moving
+    // down has to rotate the DP all the way around to the right.
+    let exit = red.exit(Direction::Down, Direction::Left).unwrap();
+    assert_eq!(exit.dp, Direction::Right);
+    assert_eq!(exit.cc, Direction::Right);
+    assert_eq!(exit.to, Position { left: 2, top: 0 });
+}
+
+#[test]
+fn precomputes_white_slide_destinations() {
+    let prog = program(&["RWWG", "KKKK"]);
+    let exit = prog.block(0).exit(Direction::Right, Direction::Left).unwrap();
+    assert_eq!(exit.from, Position { left: 0, top: 0 });
+    assert_eq!(exit.to, Position { left: 3, top: 0 });
+    assert!(exit.through_white);
+}
+
+#[test]
+fn rejects_colors_outside_the_palette() {
+    let img = RgbImage::from_pixel(2, 2, Rgb([128, 128, 128]));
+    assert!(Program::new(img).is_err());
+}
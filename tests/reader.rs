@@ -0,0 +1,184 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use std::env;
+use std::path::PathBuf;
+
+use image::{Rgb, RgbImage};
+
+use piet::{Interpreter, PietError};
+use piet::color::{self, Color};
+use piet::reader::{self, Options, UnknownColors};
+use piet::state::Position;
+
+/// The codel grid of a sample program.
+fn fixture() -> RgbImage {
+    reader::read("testdata/Piet_hello.png", &Options::default()).unwrap()
+}
+
+/// Saves `fixture` scaled up to `codel_size` pixel codels, returning the path it was saved to.
+fn save_scaled(name: &str, codel_size: u32) -> PathBuf {
+    let path = env::temp_dir().join(format!("piet-reader-{}-{}.png", name, std::process::id()));
+    reader::upsample(&fixture(), codel_size).save(&path).unwrap();
+    path
+}
+
+/// The dimensions and pixels of `img`, for comparing images.
+fn pixels(img: RgbImage) -> (u32, u32, Vec<u8>) {
+    (img.width(), img.height(), img.into_raw())
+}
+
+#[test]
+fn detects_the_codel_size() {
+    let codels = fixture();
+    assert_eq!(reader::detect_codel_size(&codels), 1);
+    assert_eq!(reader::detect_codel_size(&reader::upsample(&codels, 7)), 7);
+
+    let path = save_scaled("detect", 5);
+    let img = reader::read(&path, &Options::default()).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(pixels(img), pixels(codels));
+}
+
+#[test]
+fn reads_an_explicit_codel_size() {
+    let path = save_scaled("explicit", 4);
+    let opts = |size| {
+        Options {
+            codel_size: Some(size),
+            ..Options::default()
+        }
+    };
+    let codels = reader::read(&path, &opts(4)).unwrap();
+    // Every codel is made of 2x2 pixel squares as well
+    let halves = reader::read(&path, &opts(2)).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(pixels(codels), pixels(fixture()));
+    assert_eq!(pixels(halves), pixels(reader::upsample(&fixture(), 2)));
+}
+
+#[test]
+fn rejects_codel_sizes_not_dividing_the_image() {
+    let path = save_scaled("invalid", 4);
+    let (width, height) = (fixture().width() * 4, fixture().height() * 4);
+    for &size in &[0, 3, width + 4] {
+        let opts = Options {
+            codel_size: Some(size),
+            ..Options::default()
+        };
+        match reader::read(&path, &opts) {
+            Err(PietError::InvalidCodelSize { size: s, width: w, height: h }) => {
+                assert_eq!((s, w, h), (size, width, height));
+            }
+            res => panic!("codel size {}: {:?}", size, res.map(|img| img.dimensions())),
+        }
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn rejects_codel_sizes_splitting_codels() {
+    // Codels of 2x2 pixels, read as a single codel of 4x4
+    let path = env::temp_dir().join(format!("piet-reader-split-{}.png", std::process::id()));
+    reader::upsample(&common::codels(&["RG", "BW"]), 2).save(&path).unwrap();
+    let opts = |unknown_colors| {
+        Options {
+            codel_size: Some(4),
+            unknown_colors,
+        }
+    };
+    let exact = reader::read(&path, &opts(UnknownColors::Error));
+    let nearest = reader::read(&path, &opts(UnknownColors::Nearest));
+    let _ = std::fs::remove_file(&path);
+    match exact {
+        Err(PietError::InvalidCodelSize { size: 4, width: 4, height: 4 }) => (),
+        res => panic!("Unexpected result: {:?}", res.map(|img| img.dimensions())),
+    }
+    // Nearest mode reads noisy images, so it reads each codel from its top-left pixel instead
+    assert_eq!(pixels(nearest.unwrap()), pixels(common::codels(&["R"])));
+}
+
+/// A light red pixel, which is a piet color, then a dark gray and a pale blue one, which are
+/// not.
+fn off_colors() -> RgbImage {
+    let colors = [[255, 192, 192], [40, 40, 40], [190, 200, 250]];
+    RgbImage::from_fn(3, 1, |x, _| Rgb(colors[x as usize]))
+}
+
+fn replaced(mode: UnknownColors) -> Vec<u8> {
+    let mut img = off_colors();
+    reader::replace_unknown_colors(&mut img, mode).unwrap();
+    img.into_raw()
+}
+
+#[test]
+fn replaces_unknown_colors_with_white_or_black() {
+    assert_eq!(replaced(UnknownColors::White),
+               [255, 192, 192, 255, 255, 255, 255, 255, 255]);
+    assert_eq!(replaced(UnknownColors::Black), [255, 192, 192, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn replaces_unknown_colors_with_the_nearest_piet_color() {
+    assert_eq!(color::nearest_piet_color(&(250, 10, 10)), (255, 0, 0));
+    assert_eq!(color::nearest_piet_color(&(20, 20, 30)), (0, 0, 0));
+    assert_eq!(color::nearest_piet_color(&(245, 250, 245)), (255, 255, 255));
+    assert_eq!(color::nearest_piet_color(&(150, 0, 140)), (192, 0, 192));
+    assert_eq!(replaced(UnknownColors::Nearest),
+               [255, 192, 192, 0, 0, 0, 192, 192, 255]);
+}
+
+#[test]
+fn reports_unknown_colors_by_codel() {
+    let mut img = off_colors();
+    match reader::replace_unknown_colors(&mut img, UnknownColors::Error) {
+        Err(PietError::InvalidColor { rgb, pos }) => {
+            assert_eq!((rgb, pos), ((40, 40, 40), Some(Position { left: 1, top: 0 })));
+        }
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    // Read from a file, the position is that of the codel holding the pixel. The stray pixel
+    // is reported even when it stops the codel size from being detected, or the codel size
+    // does not fit the image.
+    let path = env::temp_dir().join(format!("piet-reader-gray-{}.png", std::process::id()));
+    let mut img = reader::upsample(&fixture(), 4);
+    img.put_pixel(9, 6, Rgb([128, 128, 128]));
+    img.save(&path).unwrap();
+    for &(codel_size, left, top) in &[(Some(4), 2, 1), (None, 9, 6), (Some(7), 1, 0)] {
+        let opts = Options {
+            codel_size,
+            ..Options::default()
+        };
+        match reader::read(&path, &opts) {
+            Err(PietError::InvalidColor { rgb, pos }) => {
+                assert_eq!((rgb, pos), ((128, 128, 128), Some(Position { left, top })));
+            }
+            res => panic!("Unexpected result: {:?}", res.map(|img| img.dimensions())),
+        }
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn reads_photos_with_the_nearest_colors() {
+    let path = "testdata/what-happened-9781501175565_lg.jpg";
+    match reader::read(path, &Options::default()) {
+        Err(PietError::InvalidColor { .. }) => (),
+        res => panic!("Unexpected result: {:?}", res.map(|img| img.dimensions())),
+    }
+    let opts = Options {
+        unknown_colors: UnknownColors::Nearest,
+        ..Options::default()
+    };
+    let img = reader::read(path, &opts).unwrap();
+    assert!(img.pixels().all(|px| Color::from_px(&(px[0], px[1], px[2])).is_ok()));
+
+    // Images made of piet colors only are left as they are
+    let img = reader::read("testdata/artistic_hello.gif", &opts).unwrap();
+    let mut interp = Interpreter::with_io(img, &b""[..], Vec::new()).unwrap();
+    interp.run().unwrap();
+    assert_eq!(String::from_utf8(interp.output().clone()).unwrap(), "Hello, world!\n");
+}
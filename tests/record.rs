@@ -0,0 +1,57 @@
+extern crate gif;
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use std::time::Duration;
+
+use image::Rgb;
+
+use piet::Interpreter;
+use piet::record::{self, Recorder};
+
+#[test]
+fn outlines_the_current_block_and_lists_the_stack() {
+    let mut interp = Interpreter::with_io(common::push_then_out_char(), &b""[..], Vec::new())
+        .unwrap();
+    interp.step().unwrap();
+    let frame = record::render_frame(interp.program(), interp.state(), 10, true);
+    assert_eq!(frame.dimensions(), record::frame_size(interp.program(), 10, true));
+
+    // The dark red block's edge is outlined, its middle is not
+    assert_ne!(frame.get_pixel(10, 0), &Rgb([192, 0, 0]));
+    assert_eq!(frame.get_pixel(11, 5), &Rgb([192, 0, 0]));
+    // The first block is left as it is
+    assert_eq!(frame.get_pixel(0, 0), &Rgb([255, 0, 0]));
+    // The sidebar shows the 1 that was pushed
+    assert!((30..frame.width()).any(|x| frame.get_pixel(x, 5) != frame.get_pixel(31, 0)));
+}
+
+#[test]
+fn writes_a_frame_per_step() {
+    let mut interp = Interpreter::with_io(common::push_then_out_char(), &b""[..], Vec::new())
+        .unwrap();
+    let mut gif = Vec::new();
+    {
+        let mut recorder = Recorder::new(&mut gif,
+                                         interp.program(),
+                                         4,
+                                         false,
+                                         Duration::from_millis(50))
+            .unwrap();
+        recorder.write_frame(interp.program(), interp.state()).unwrap();
+        for _ in 0..3 {
+            interp.step().unwrap();
+            recorder.write_frame(interp.program(), interp.state()).unwrap();
+        }
+    }
+
+    let mut decoder = gif::Decoder::new(&gif[..]).read_info().unwrap();
+    let mut frames = 0;
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        assert_eq!((frame.width, frame.height, frame.delay), (12, 4, 5));
+        frames += 1;
+    }
+    assert_eq!(frames, 4);
+}
@@ -0,0 +1,17 @@
+extern crate piet;
+
+use piet::state::{Direction, State};
+
+#[test]
+fn toggles_the_cc_it_was_set_to() {
+    let mut state = State::new();
+    state.set_cc(Direction::Right);
+    state.toggle_cc();
+    assert_eq!(state.cc(), Direction::Left);
+}
+
+#[test]
+#[should_panic(expected = "The CC must be left or right")]
+fn rejects_a_cc_that_is_not_left_or_right() {
+    State::new().set_cc(Direction::Up);
+}
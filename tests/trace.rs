@@ -0,0 +1,57 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use image::RgbImage;
+
+use piet::Interpreter;
+use piet::asm;
+use piet::trace;
+
+/// Traces every step of the program in `img` until it terminates.
+fn trace_all(img: RgbImage) -> String {
+    let mut interp = Interpreter::with_io(img, &b""[..], Vec::new()).unwrap();
+    let mut out = Vec::new();
+    let mut n = 0;
+    while let Some(step) = interp.step().unwrap() {
+        trace::write_step(&mut out, n, &step, &interp.state().stack).unwrap();
+        n += 1;
+    }
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn writes_npiet_style_lines() {
+    let mut interp = Interpreter::with_io(common::push_then_out_char(), &b""[..], Vec::new())
+        .unwrap();
+
+    let mut out = Vec::new();
+    for n in 0..2 {
+        let step = interp.step().unwrap().unwrap();
+        trace::write_step(&mut out, n, &step, &interp.state().stack).unwrap();
+    }
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "trace: step 0  (0,0/r,l nR -> 1,0/r,l dR):\n\
+                action: push, value 1\n\
+                trace: stack (1 values): 1\n\
+                trace: step 1  (1,0/r,l dR -> 2,0/r,l nM):\n\
+                action: out(char)\n\
+                trace: stack (0 values):\n");
+}
+
+#[test]
+fn traces_pointer_steps_until_the_program_ends() {
+    // A full turn of the DP, then a slide through white into the block the program ends in
+    let img = asm::assemble(&asm::parse("push 4\npointer").unwrap()).unwrap();
+    assert_eq!(trace_all(img),
+               "trace: step 0  (1,0/r,l lR -> 2,0/r,l nR):\n\
+                action: push, value 4\n\
+                trace: stack (1 values): 4\n\
+                trace: step 1  (2,0/r,l nR -> 3,0/r,l dC):\n\
+                action: pointer\n\
+                trace: stack (0 values):\n\
+                trace: step 2  (3,0/r,l dC -> 6,0/r,l dC):\n\
+                trace: white cell(s) crossed - continuing with no command\n\
+                trace: stack (0 values):\n");
+}
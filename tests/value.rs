@@ -0,0 +1,37 @@
+extern crate piet;
+
+use piet::{Interpreter, StackValue};
+use piet::asm;
+
+fn run(src: &str) -> String {
+    let img = asm::assemble(&asm::parse(src).unwrap()).unwrap();
+    let mut interp = Interpreter::with_io(img, &b""[..], Vec::new()).unwrap();
+    interp.run().unwrap();
+    String::from_utf8(interp.output().clone()).unwrap()
+}
+
+#[test]
+fn division_without_a_representable_result_fails() {
+    assert_eq!(7i64.try_div(&0), None);
+    assert_eq!(i64::MIN.try_div(&-1), None);
+    assert_eq!(i64::MIN.try_div(&1), Some(i64::MIN));
+    assert_eq!(i64::MIN.try_mod(&-1), Some(0));
+}
+
+#[test]
+fn drops_characters_beyond_u32() {
+    // 2^32 + 65 is not a character, although its lower 32 bits are 'A'
+    assert_eq!(run("push 4294967361\nout char\npush 66\nout char"), "B");
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn computes_factorials_beyond_i64() {
+    // 25! is far larger than i64::MAX
+    let mut src = "push 1\n".to_string();
+    for n in 2..26 {
+        src.push_str(&format!("push {}\nmul\n", n));
+    }
+    src.push_str("out number");
+    assert_eq!(run(&src), "15511210043330985984000000");
+}
@@ -0,0 +1,24 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use piet::Interpreter;
+use piet::visual;
+
+#[test]
+fn renders_two_codels_per_line_with_status_below() {
+    // Red and dark red over a row of black: push 1, then bounce back and forth
+    let img = common::codels(&["R-R", "KK", "KK"]);
+    let mut interp = Interpreter::with_io(img, &b""[..], Vec::new()).unwrap();
+    interp.step().unwrap();
+
+    let frame = visual::render_frame(&interp, "hello\nworld");
+    let lines: Vec<&str> = frame.lines().collect();
+    assert_eq!(lines[0].matches('▀').count(), 2);
+    assert_eq!(lines[1].matches('▀').count(), 2);
+    assert!(lines[2].starts_with("Step 1  DP →  CC left  at (1, 0)"));
+    assert!(lines[2].ends_with("next: pop"));
+    assert_eq!(lines[3], "Stack (top first): 1");
+    assert_eq!(&lines[4..], ["Output:", "hello", "world"]);
+}
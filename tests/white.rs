@@ -0,0 +1,62 @@
+extern crate image;
+extern crate piet;
+
+mod common;
+
+use piet::state::{Direction, Position};
+use piet::{Command, Interpreter, Step};
+
+fn run(rows: &[&str], max_steps: usize) -> (Vec<Step>, bool) {
+    let mut interp = Interpreter::with_io(common::codels(rows), &b""[..], Vec::new()).unwrap();
+    let steps: Vec<Step> = interp.by_ref().take(max_steps).collect();
+    assert!(interp.error().is_none());
+    (steps, interp.is_finished())
+}
+
+#[test]
+fn slides_through_white_without_executing_a_command() {
+    let (steps, _) = run(&["RRWWWGK"], 1);
+    assert_eq!(steps[0].cmd, Command::Nop);
+    assert_eq!(steps[0].from_pos, Position { left: 1, top: 0 });
+    assert_eq!(steps[0].to_pos, Position { left: 5, top: 0 });
+    assert_eq!(steps[0].to_color.mnemonic, "Green");
+    assert!(steps[0].pushed.is_empty());
+}
+
+#[test]
+fn restriction_in_white_toggles_cc_and_rotates_dp() {
+    let (steps, _) = run(&["RWW", "KKW", "KKG"], 1);
+    assert_eq!(steps[0].to_pos, Position { left: 2, top: 2 });
+    assert_eq!(steps[0].dp_after, Direction::Down);
+    assert_eq!(steps[0].cc_after, Direction::Right);
+}
+
+#[test]
+fn starting_in_white_slides_to_first_color_block() {
+    let (steps, _) = run(&["WWB", "KKK"], 1);
+    assert_eq!(steps[0].from_pos, Position { left: 0, top: 0 });
+    assert_eq!(steps[0].to_pos, Position { left: 2, top: 0 });
+}
+
+#[test]
+fn terminates_when_trapped_in_white() {
+    let (steps, finished) = run(&["WWW", "WWW", "WWW"], 10);
+    assert!(steps.is_empty());
+    assert!(finished);
+}
+
+#[test]
+fn terminates_when_white_never_leads_to_another_color_block() {
+    // The only exit from the red block leads into a white region whose slides never reach
+    // another colored codel.
+    let (steps, finished) = run(&["RWW", "KWW"], 10);
+    assert!(steps.is_empty());
+    assert!(finished);
+}
+
+#[test]
+fn terminates_when_every_exit_is_restricted() {
+    let (steps, finished) = run(&["KKK", "KRK", "KKK"], 10);
+    assert!(steps.is_empty());
+    assert!(finished);
+}